@@ -72,8 +72,16 @@ pub unsafe extern "C" fn classifier_free(classifier: *mut Classifier) {
     Box::from_raw(classifier);
 }
 
+/// Learns the rejection thresholds `scores_unknown` compares against, `tolerance` standard
+/// deviations above each symbol's mean distance
+#[no_mangle]
+pub unsafe extern "C" fn classifier_learn_thresholds(classifier: *mut Classifier, tolerance: f64) {
+    (*classifier).learn_thresholds(tolerance);
+}
+
 pub struct Scores {
     scores: Vec<Score>,
+    unknown: bool,
 }
 
 /// Classifiy the sample returning scores and free's `sample`
@@ -82,8 +90,12 @@ pub unsafe extern "C" fn classify(
     classifier: *mut Classifier,
     sample: *mut StrokeSample,
 ) -> *mut Scores {
-    match (*classifier).classify(*Box::from_raw(sample)) {
-        Some(scores) => Box::into_raw(Box::new(Scores { scores })),
+    let classifier = &*classifier;
+    match classifier.classify(*Box::from_raw(sample)) {
+        Some(scores) => {
+            let unknown = scores.first().is_some_and(|best| classifier.rejects(best));
+            Box::into_raw(Box::new(Scores { scores, unknown }))
+        }
         None => ptr::null_mut(),
     }
 }
@@ -94,16 +106,32 @@ pub unsafe extern "C" fn scores_length(scores: *mut Scores) -> usize {
     (*scores).scores.len()
 }
 
+/// Returns whether the best match is further than its learned threshold, always false before
+/// `classifier_learn_thresholds`
+#[no_mangle]
+pub unsafe extern "C" fn scores_unknown(scores: *mut Scores) -> bool {
+    (*scores).unknown
+}
+
 /// Returns the `i`-th score of `scores`
 #[no_mangle]
 pub unsafe extern "C" fn scores_get_score(scores: *mut Scores, i: usize) -> f64 {
-    (*scores).scores.get_unchecked(i).score
+    let scores = &*scores;
+    scores.scores.get_unchecked(i).score
+}
+
+/// Returns the `i`-th confidence of `scores`
+#[no_mangle]
+pub unsafe extern "C" fn scores_get_confidence(scores: *mut Scores, i: usize) -> f64 {
+    let scores = &*scores;
+    scores.scores.get_unchecked(i).confidence
 }
 
 /// Returns the `i`-th symbol of `scores`, callers responsible for calling `symbol_free` once finished
 #[no_mangle]
 pub unsafe extern "C" fn scores_get_symbol(scores: *mut Scores, i: usize) -> *const Symbol {
-    match Symbol::from_id(&(*scores).scores[i].id) {
+    let scores = &*scores;
+    match Symbol::from_id(&scores.scores[i].id) {
         Some(symbol) => Box::into_raw(Box::new(symbol)),
         None => ptr::null_mut(),
     }
@@ -0,0 +1,318 @@
+mod snapshot;
+
+use clap::{Parser, Subcommand};
+use detexify::{
+    iter_symbols, svg, to_base32_ids, Classifier, Mode, Stroke, StrokeSample, Symbol, SymbolFilter,
+};
+use serde_json::json;
+use snapshot::SnapshotCommand;
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+
+/// Classify drawings and look up symbols without running the server
+#[derive(Debug, Parser)]
+#[command(name = "detexify", version)]
+struct Args {
+    /// Print JSON instead of a table
+    #[arg(long, global = true)]
+    json: bool,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Classify strokes, a JSON array of strokes each an array of `{"x": .., "y": ..}` points
+    Classify {
+        /// File to read the strokes from, `-` or unset for stdin
+        strokes: Option<PathBuf>,
+        /// Number of results to print
+        #[arg(short = 'k', long, default_value_t = 5)]
+        top: usize,
+        /// Snapshot to classify with instead of the embedded one
+        #[arg(long)]
+        snapshot: Option<PathBuf>,
+        /// Softmax temperature turning scores into confidences, see `calibrate`
+        #[arg(long)]
+        temperature: Option<f64>,
+        /// Learn rejection thresholds with this tolerance and report whether the drawing matches
+        /// no symbol well
+        #[arg(long)]
+        reject_tolerance: Option<f64>,
+    },
+    /// Fit the temperature turning scores into confidences to labelled samples held out from the
+    /// snapshot, for the server's `temperature` setting
+    Calibrate {
+        /// Labelled samples in the snapshot format
+        labelled: PathBuf,
+        /// Snapshot to calibrate instead of the embedded one
+        #[arg(long)]
+        snapshot: Option<PathBuf>,
+    },
+    /// List the symbol table, optionally searching the commands
+    Symbols {
+        /// Case-insensitive search of the commands
+        query: Option<String>,
+        /// Only symbols from this package
+        #[arg(long)]
+        package: Option<String>,
+        /// Only symbols usable in this mode, `math` or `text`
+        #[arg(long)]
+        mode: Option<Mode>,
+    },
+    /// Show a symbol
+    Info {
+        /// Symbol id
+        id: String,
+    },
+    /// Render strokes to SVG next to the points they preprocess to, numbered in drawing order
+    Render {
+        /// File to read the strokes from, `-` or unset for stdin
+        strokes: Option<PathBuf>,
+        /// SVG file to write, stdout when unset
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        /// Draw the strokes after every preprocessing stage
+        #[arg(long)]
+        stages: bool,
+    },
+    /// Inspect, merge and convert snapshots
+    Snapshot {
+        #[command(subcommand)]
+        command: SnapshotCommand,
+    },
+}
+
+fn symbol_json(symbol: &Symbol) -> serde_json::Value {
+    json!({
+        "id": symbol.id(),
+        "command": symbol.command,
+        "package": symbol.package,
+        "font_encoding": symbol.font_encoding,
+        "text_mode": symbol.text_mode,
+        "math_mode": symbol.math_mode,
+    })
+}
+
+fn print_json(value: &serde_json::Value) -> io::Result<()> {
+    println!("{}", serde_json::to_string_pretty(value)?);
+    Ok(())
+}
+
+/// Reads strokes from `path`, stdin if unset or `-`
+fn read_strokes(path: Option<&Path>) -> io::Result<Vec<Stroke>> {
+    let mut input = String::new();
+    match path {
+        Some(path) if path != Path::new("-") => File::open(path)?.read_to_string(&mut input)?,
+        _ => io::stdin().read_to_string(&mut input)?,
+    };
+
+    Ok(serde_json::from_str(&input)?)
+}
+
+/// Reads the classifier from `snapshot`, the embedded one if unset
+fn read_classifier(snapshot: Option<&Path>) -> io::Result<Classifier> {
+    match snapshot {
+        Some(path) => Ok(Classifier::from_snapshot(BufReader::new(File::open(
+            path,
+        )?))?),
+        None => Ok(Classifier::default()),
+    }
+}
+
+fn classify(
+    args: &Args,
+    strokes: Option<&Path>,
+    top: usize,
+    snapshot: Option<&Path>,
+    temperature: Option<f64>,
+    reject_tolerance: Option<f64>,
+) -> io::Result<()> {
+    let sample = StrokeSample::new(read_strokes(strokes)?)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "strokes have no points"))?;
+    let mut classifier = read_classifier(snapshot)?;
+    if let Some(temperature) = temperature {
+        if !(temperature > 0.0 && temperature.is_finite()) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "temperature must be a positive number",
+            ));
+        }
+        classifier.set_temperature(temperature);
+    }
+    if let Some(tolerance) = reject_tolerance {
+        if !(tolerance >= 0.0 && tolerance.is_finite()) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "reject tolerance must be a non-negative number",
+            ));
+        }
+        classifier.learn_thresholds(tolerance);
+    }
+
+    let scores = classifier.classify(sample).unwrap_or_default();
+    let unknown = scores.first().is_some_and(|best| classifier.rejects(best));
+    let scores = scores
+        .into_iter()
+        .take(top)
+        .map(|score| (Symbol::from_id(&score.id), score));
+
+    if args.json {
+        let results: Vec<_> = scores
+            .map(|(symbol, score)| {
+                json!({
+                    "id": score.id,
+                    "score": score.score,
+                    "confidence": score.confidence,
+                    "symbol": symbol.as_ref().map(symbol_json),
+                })
+            })
+            .collect();
+        return match reject_tolerance {
+            Some(_) => print_json(&json!({ "unknown": unknown, "results": results })),
+            None => print_json(&json!(results)),
+        };
+    }
+
+    if unknown {
+        println!("no symbol matches well, the closest are:");
+    }
+    for (rank, (symbol, score)) in scores.enumerate() {
+        let (command, package) = match &symbol {
+            Some(symbol) => (symbol.command, symbol.package),
+            None => (score.id.as_str(), "?"),
+        };
+        println!(
+            "{:>2}. {:<30} {:<16} {:>5.1}%",
+            rank + 1,
+            command,
+            package,
+            100.0 * score.confidence
+        );
+    }
+
+    Ok(())
+}
+
+fn calibrate(args: &Args, labelled: &Path, snapshot: Option<&Path>) -> io::Result<()> {
+    let labelled: HashMap<String, Vec<StrokeSample>> =
+        serde_json::from_reader(BufReader::new(File::open(labelled)?))?;
+    let labelled =
+        to_base32_ids(labelled).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    let samples: usize = labelled.values().map(Vec::len).sum();
+
+    let mut classifier = read_classifier(snapshot)?;
+    let temperature = classifier.calibrate(&labelled);
+
+    if args.json {
+        return print_json(&json!({ "temperature": temperature, "samples": samples }));
+    }
+
+    println!("temperature: {}", temperature);
+    println!("fitted to {} labelled samples", samples);
+
+    Ok(())
+}
+
+fn symbols(
+    args: &Args,
+    query: Option<&str>,
+    package: Option<&str>,
+    mode: Option<Mode>,
+) -> io::Result<()> {
+    let filter = SymbolFilter {
+        packages: package.map(|p| vec![p.to_string()]),
+        mode,
+        query: query.map(str::to_string),
+    };
+    let mut symbols: Vec<Symbol> = iter_symbols().filter(|s| filter.matches(s)).collect();
+    symbols.sort_by(|a, b| (a.command, a.package).cmp(&(b.command, b.package)));
+
+    if args.json {
+        return print_json(&json!(symbols.iter().map(symbol_json).collect::<Vec<_>>()));
+    }
+
+    for symbol in &symbols {
+        println!(
+            "{:<30} {:<16} {}",
+            symbol.command,
+            symbol.package,
+            symbol.id()
+        );
+    }
+
+    Ok(())
+}
+
+fn info(args: &Args, id: &str) -> io::Result<()> {
+    let symbol = Symbol::from_id(id).ok_or_else(|| {
+        io::Error::new(io::ErrorKind::NotFound, format!("unknown symbol id {}", id))
+    })?;
+
+    if args.json {
+        return print_json(&symbol_json(&symbol));
+    }
+
+    println!("command:       {}", symbol.command);
+    println!("package:       {}", symbol.package);
+    println!("font encoding: {}", symbol.font_encoding);
+    println!("math mode:     {}", symbol.math_mode);
+    println!("text mode:     {}", symbol.text_mode);
+
+    Ok(())
+}
+
+fn render(strokes: Option<&Path>, output: Option<&Path>, stages: bool) -> io::Result<()> {
+    let strokes = read_strokes(strokes)?;
+    let svg = if stages {
+        svg::render_stages(&strokes)
+    } else {
+        svg::render_preprocessing(&strokes)
+    };
+    match output {
+        Some(path) => fs::write(path, svg),
+        None => io::stdout().write_all(svg.as_bytes()),
+    }
+}
+
+fn run(args: &Args) -> io::Result<()> {
+    match &args.command {
+        Command::Classify {
+            strokes,
+            top,
+            snapshot,
+            temperature,
+            reject_tolerance,
+        } => classify(
+            args,
+            strokes.as_deref(),
+            *top,
+            snapshot.as_deref(),
+            *temperature,
+            *reject_tolerance,
+        ),
+        Command::Calibrate { labelled, snapshot } => calibrate(args, labelled, snapshot.as_deref()),
+        Command::Symbols {
+            query,
+            package,
+            mode,
+        } => symbols(args, query.as_deref(), package.as_deref(), *mode),
+        Command::Info { id } => info(args, id),
+        Command::Render {
+            strokes,
+            output,
+            stages,
+        } => render(strokes.as_deref(), output.as_deref(), *stages),
+        Command::Snapshot { command } => snapshot::run(args.json, command),
+    }
+}
+
+fn main() {
+    if let Err(e) = run(&Args::parse()) {
+        eprintln!("{}", e);
+        process::exit(1);
+    }
+}
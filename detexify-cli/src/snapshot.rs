@@ -0,0 +1,267 @@
+use clap::{Subcommand, ValueEnum};
+use detexify::{base32_to_base64, base64_to_base32, iter_symbols, Classifier, IdEncoding, Symbol};
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Subcommand)]
+pub enum SnapshotCommand {
+    /// Samples per symbol, symbols without samples and ids that aren't in the symbol table
+    Stats { snapshot: PathBuf },
+    /// Combine snapshots, samples of earlier snapshots are kept first
+    Merge {
+        #[arg(required = true)]
+        snapshots: Vec<PathBuf>,
+        /// Snapshot to write
+        #[arg(short, long)]
+        output: PathBuf,
+        /// Maximum number of samples kept per symbol
+        #[arg(long, default_value_t = detexify::DEFAULT_SAMPLE_LIMIT)]
+        sample_limit: usize,
+    },
+    /// Re-encode the ids of a snapshot
+    Convert {
+        snapshot: PathBuf,
+        /// Snapshot to write
+        #[arg(short, long)]
+        output: PathBuf,
+        /// Encoding to convert the ids to, they are read in the other one
+        #[arg(long)]
+        to: Encoding,
+    },
+}
+
+/// Id encodings, base32 is used by this crate and base64 by the original detexify snapshots
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+pub enum Encoding {
+    Base32,
+    Base64,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct SnapshotStats {
+    /// Samples of each symbol with samples, fewest first
+    pub samples: Vec<(String, usize)>,
+    /// Ids of symbols in the table without samples, sorted
+    pub missing: Vec<&'static str>,
+    /// Ids with samples that aren't in the symbol table, sorted
+    pub unknown: Vec<String>,
+}
+
+impl SnapshotStats {
+    pub fn new(classifier: &Classifier) -> SnapshotStats {
+        let mut samples: Vec<(String, usize)> = classifier
+            .sample_counts()
+            .map(|(id, count)| (id.to_string(), count))
+            .collect();
+        samples.sort_by(|a, b| (a.1, &a.0).cmp(&(b.1, &b.0)));
+
+        let counts: HashMap<&str, usize> = classifier.sample_counts().collect();
+        let mut missing: Vec<&'static str> = iter_symbols()
+            .map(|symbol| symbol.id())
+            .filter(|id| !counts.contains_key(id))
+            .collect();
+        missing.sort_unstable();
+
+        let mut unknown: Vec<String> = samples
+            .iter()
+            .filter(|(id, _)| Symbol::from_id(id).is_none())
+            .map(|(id, _)| id.clone())
+            .collect();
+        unknown.sort();
+
+        SnapshotStats {
+            samples,
+            missing,
+            unknown,
+        }
+    }
+}
+
+fn read(path: &Path) -> io::Result<Classifier> {
+    Ok(Classifier::from_snapshot(BufReader::new(File::open(
+        path,
+    )?))?)
+}
+
+fn write(path: &Path, classifier: &Classifier) -> io::Result<()> {
+    let mut output = BufWriter::new(File::create(path)?);
+    classifier.to_snapshot(&mut output)?;
+    output.into_inner()?.sync_all()
+}
+
+/// Re-encodes every id of a snapshot, leaving the samples as they are
+pub fn convert(snapshot: Map<String, Value>, to: Encoding) -> io::Result<Map<String, Value>> {
+    let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidData, message);
+    let target = match to {
+        Encoding::Base32 => IdEncoding::Base32,
+        Encoding::Base64 => IdEncoding::Base64,
+    };
+    let keys: Vec<String> = snapshot.keys().cloned().collect();
+    let detected = IdEncoding::detect(&keys).map_err(|e| invalid(e.to_string()))?;
+    // converting again would corrupt ids that happen to be valid in both encodings
+    if detected == target {
+        return Err(invalid(format!("the snapshot ids are already {}", target)));
+    }
+
+    snapshot
+        .into_iter()
+        .map(|(id, samples)| {
+            let converted = match to {
+                Encoding::Base32 => base64_to_base32(&id),
+                Encoding::Base64 => base32_to_base64(&id),
+            };
+            let converted = converted.ok_or_else(|| invalid(format!("invalid id {}", id)))?;
+
+            Ok((converted, samples))
+        })
+        .collect()
+}
+
+fn command(id: &str) -> &str {
+    Symbol::from_id(id).map_or("?", |symbol| symbol.command)
+}
+
+fn stats(json: bool, path: &Path) -> io::Result<()> {
+    let stats = SnapshotStats::new(&read(path)?);
+
+    if json {
+        let samples: Map<String, Value> = stats
+            .samples
+            .iter()
+            .map(|(id, count)| (id.clone(), json!(count)))
+            .collect();
+        return crate::print_json(&json!({
+            "samples": samples,
+            "missing": stats.missing,
+            "unknown": stats.unknown,
+        }));
+    }
+
+    let total: usize = stats.samples.iter().map(|(_, count)| count).sum();
+    println!(
+        "{} samples of {} symbols, {} symbols without samples, {} unknown ids",
+        total,
+        stats.samples.len(),
+        stats.missing.len(),
+        stats.unknown.len()
+    );
+
+    println!("\nsamples per symbol:");
+    for (id, count) in &stats.samples {
+        println!("{:>6} {:<30} {}", count, command(id), id);
+    }
+
+    println!("\nsymbols without samples:");
+    for id in &stats.missing {
+        println!("{:<37} {}", command(id), id);
+    }
+
+    println!("\nids not in the symbol table:");
+    for id in &stats.unknown {
+        println!("{}", id);
+    }
+
+    Ok(())
+}
+
+fn merge(snapshots: &[PathBuf], output: &Path, sample_limit: usize) -> io::Result<()> {
+    if sample_limit == 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "--sample-limit must be greater than 0",
+        ));
+    }
+
+    let mut merged = Classifier::new().with_sample_limit(sample_limit);
+    for path in snapshots {
+        merged.merge(read(path)?);
+    }
+    write(output, &merged)?;
+
+    println!(
+        "merged {} snapshots into {} samples of {} symbols",
+        snapshots.len(),
+        merged.sample_count(),
+        merged.symbol_count()
+    );
+
+    Ok(())
+}
+
+fn convert_file(path: &Path, output: &Path, to: Encoding) -> io::Result<()> {
+    let snapshot: Map<String, Value> = serde_json::from_reader(BufReader::new(File::open(path)?))?;
+    let snapshot = convert(snapshot, to)?;
+
+    let mut writer = BufWriter::new(File::create(output)?);
+    serde_json::to_writer(&mut writer, &snapshot)?;
+    writer.into_inner()?.sync_all()
+}
+
+pub fn run(json: bool, command: &SnapshotCommand) -> io::Result<()> {
+    match command {
+        SnapshotCommand::Stats { snapshot } => stats(json, snapshot),
+        SnapshotCommand::Merge {
+            snapshots,
+            output,
+            sample_limit,
+        } => merge(snapshots, output, *sample_limit),
+        SnapshotCommand::Convert {
+            snapshot,
+            output,
+            to,
+        } => convert_file(snapshot, output, *to),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use detexify::{Point, Stroke, StrokeSample};
+
+    const TEXTASCIICIRCUM: &str = "NRQXIZLYGJSS2T2UGEWV65DFPB2GC43DNFUWG2LSMN2W2";
+
+    #[test]
+    fn test_stats() {
+        let sample = StrokeSample::new(vec![Stroke::new(vec![
+            Point { x: 0.0, y: 0.0 },
+            Point { x: 1.0, y: 1.0 },
+        ])])
+        .unwrap();
+
+        let mut classifier = Classifier::new();
+        classifier.train(TEXTASCIICIRCUM.to_string(), sample.clone());
+        classifier.train(TEXTASCIICIRCUM.to_string(), sample.clone());
+        classifier.train("unknown".to_string(), sample);
+
+        let stats = SnapshotStats::new(&classifier);
+        assert_eq!(
+            stats.samples,
+            vec![("unknown".to_string(), 1), (TEXTASCIICIRCUM.to_string(), 2)]
+        );
+        assert_eq!(stats.missing.len(), iter_symbols().count() - 1);
+        assert!(!stats.missing.contains(&TEXTASCIICIRCUM));
+        assert_eq!(stats.unknown, vec!["unknown"]);
+    }
+
+    #[test]
+    fn test_convert() {
+        let mut snapshot = Map::new();
+        snapshot.insert(TEXTASCIICIRCUM.to_string(), json!([]));
+
+        let base64 = convert(snapshot.clone(), Encoding::Base64).unwrap();
+        assert!(base64.contains_key("bGF0ZXgyZS1PVDEtX3RleHRhc2NpaWNpcmN1bQ=="));
+        assert_eq!(convert(base64.clone(), Encoding::Base32).unwrap(), snapshot);
+
+        // the id is valid base64 too, converting it again would corrupt it
+        let error = convert(snapshot.clone(), Encoding::Base32).unwrap_err();
+        assert_eq!(error.to_string(), "the snapshot ids are already base32");
+        assert!(convert(base64.clone(), Encoding::Base64).is_err());
+
+        let mut mixed = base64;
+        mixed.extend(snapshot);
+        assert!(convert(mixed, Encoding::Base64).is_err());
+    }
+}
@@ -0,0 +1,88 @@
+use clap::Parser;
+use detexify::Classifier;
+use detexify_server::feedback;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter};
+use std::num::NonZeroUsize;
+use std::path::PathBuf;
+use std::process;
+
+/// Turns a feedback dataset recorded by `POST /feedback` into a training snapshot
+#[derive(Debug, Parser)]
+#[command(version)]
+struct Args {
+    /// Feedback dataset
+    feedback: PathBuf,
+    /// Snapshot to write
+    #[arg(short, long)]
+    output: PathBuf,
+    /// Snapshot to add the feedback to, starts from an empty classifier when unset
+    #[arg(long)]
+    base: Option<PathBuf>,
+    /// Maximum number of samples kept per symbol, the newest feedback is kept
+    #[arg(long)]
+    sample_limit: Option<NonZeroUsize>,
+}
+
+fn run(args: Args) -> io::Result<()> {
+    let mut classifier = match &args.base {
+        Some(path) => Classifier::from_snapshot(BufReader::new(File::open(path)?))?,
+        None => Classifier::new(),
+    };
+    if let Some(sample_limit) = args.sample_limit {
+        classifier = classifier.with_sample_limit(sample_limit.get());
+    }
+
+    let stats = feedback::export(BufReader::new(File::open(&args.feedback)?), &mut classifier)?;
+
+    let mut output = BufWriter::new(File::create(&args.output)?);
+    classifier.to_snapshot(&mut output)?;
+    output.into_inner()?.sync_all()?;
+
+    let ranked = stats.records - stats.unranked;
+    println!(
+        "exported {} samples, skipped {}",
+        stats.exported, stats.skipped
+    );
+    if ranked > 0 {
+        println!(
+            "chosen symbol was the first suggestion in {:.1}% of ranked records, not suggested in {}",
+            100.0 * stats.top1 as f64 / ranked as f64,
+            stats.unranked
+        );
+    }
+
+    Ok(())
+}
+
+fn main() {
+    if let Err(e) = run(Args::parse()) {
+        eprintln!("{}", e);
+        process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sample_limit() {
+        let parse = |limit: &str| {
+            Args::try_parse_from([
+                "export",
+                "feedback.jsonl",
+                "-o",
+                "out.json",
+                "--sample-limit",
+                limit,
+            ])
+        };
+
+        assert_eq!(parse("3").unwrap().sample_limit, NonZeroUsize::new(3));
+        assert_eq!(
+            parse("0").unwrap_err().kind(),
+            clap::error::ErrorKind::ValueValidation
+        );
+    }
+}
@@ -0,0 +1,217 @@
+use crate::symbols::SymbolInfo;
+use detexify::{Mode, Score, Stroke, Symbol, SymbolFilter};
+use serde::{Deserialize, Serialize};
+
+/// Body of `POST /classify`, either the bare array of strokes or an envelope with options
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum ClassifyRequest {
+    Strokes(Vec<Stroke>),
+    Envelope {
+        strokes: Vec<Stroke>,
+        #[serde(flatten)]
+        options: ClassifyOptions,
+    },
+}
+
+impl ClassifyRequest {
+    /// Splits the request into its strokes and options, envelope options take precedence over
+    /// `defaults` (the query parameters)
+    pub fn into_parts(self, defaults: ClassifyOptions) -> (Vec<Stroke>, ClassifyOptions) {
+        match self {
+            ClassifyRequest::Strokes(strokes) => (strokes, defaults),
+            ClassifyRequest::Envelope { strokes, options } => (
+                strokes,
+                ClassifyOptions {
+                    limit: options.limit.or(defaults.limit),
+                    mode: options.mode.or(defaults.mode),
+                    packages: options.packages.or(defaults.packages),
+                    reject: options.reject.or(defaults.reject),
+                },
+            ),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct ClassifyOptions {
+    /// Maximum number of results
+    pub limit: Option<usize>,
+    /// Only return symbols usable in this mode
+    pub mode: Option<Mode>,
+    /// Only return symbols from these packages
+    pub packages: Option<Vec<String>>,
+    /// Report whether the sample was rejected as not a symbol, see [`ClassifyResponse`]
+    pub reject: Option<bool>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ClassifyResult {
+    pub id: String,
+    pub score: f64,
+    pub confidence: f64,
+    /// `null` if the classifier has samples for an id that isn't in the symbol table
+    pub symbol: Option<SymbolInfo>,
+}
+
+/// Response of the classify routes, the results are wrapped with whether the best match exceeds
+/// its symbol's rejection threshold when `reject` is set
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+pub enum ClassifyResponse {
+    Results(Vec<ClassifyResult>),
+    Rejection {
+        unknown: bool,
+        results: Vec<ClassifyResult>,
+    },
+}
+
+impl ClassifyResponse {
+    pub fn into_results(self) -> Vec<ClassifyResult> {
+        match self {
+            ClassifyResponse::Results(results) => results,
+            ClassifyResponse::Rejection { results, .. } => results,
+        }
+    }
+}
+
+/// Builds the response to a classification, `unknown` is whether the sample was rejected
+pub fn response(scores: Vec<Score>, unknown: bool, options: ClassifyOptions) -> ClassifyResponse {
+    let reject = options.reject.unwrap_or(false);
+    let results = results(scores, options);
+    if reject {
+        ClassifyResponse::Rejection { unknown, results }
+    } else {
+        ClassifyResponse::Results(results)
+    }
+}
+
+/// Filters and truncates `scores` according to `options`, resolving their symbols. Scores for
+/// ids that aren't in the symbol table are dropped when filtering by mode or package.
+pub fn results(scores: Vec<Score>, options: ClassifyOptions) -> Vec<ClassifyResult> {
+    let filtered = options.mode.is_some() || options.packages.is_some();
+    let filter = SymbolFilter {
+        packages: options.packages,
+        mode: options.mode,
+        query: None,
+    };
+
+    scores
+        .into_iter()
+        .filter_map(|score| {
+            let symbol = Symbol::from_id(&score.id);
+            let keep = match &symbol {
+                Some(symbol) => filter.matches(symbol),
+                None => !filtered,
+            };
+
+            if keep {
+                Some(ClassifyResult {
+                    symbol: symbol.map(|symbol| SymbolInfo::new(&score.id, symbol)),
+                    id: score.id,
+                    score: score.score,
+                    confidence: score.confidence,
+                })
+            } else {
+                None
+            }
+        })
+        .take(options.limit.unwrap_or(usize::MAX))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEXTLESS: &str = "NRQXIZLYGJSS2T2UGEWV65DFPB2GYZLTOM";
+
+    fn score(id: &str, score: f64) -> Score {
+        Score {
+            id: id.to_string(),
+            score,
+            confidence: 0.5,
+        }
+    }
+
+    #[test]
+    fn test_request_shapes() {
+        let bare: ClassifyRequest = serde_json::from_str(r#"[[{"x": 0, "y": 0}]]"#).unwrap();
+        let (strokes, options) = bare.into_parts(ClassifyOptions {
+            limit: Some(3),
+            ..ClassifyOptions::default()
+        });
+        assert_eq!(strokes.len(), 1);
+        assert_eq!(options.limit, Some(3));
+
+        let envelope: ClassifyRequest = serde_json::from_str(
+            r#"{"strokes": [[{"x": 0, "y": 0}]], "limit": 5, "mode": "text"}"#,
+        )
+        .unwrap();
+        let (strokes, options) = envelope.into_parts(ClassifyOptions {
+            limit: Some(3),
+            packages: Some(vec!["amssymb".to_string()]),
+            ..ClassifyOptions::default()
+        });
+        assert_eq!(strokes.len(), 1);
+        assert_eq!(options.limit, Some(5));
+        assert_eq!(options.mode, Some(Mode::Text));
+        assert_eq!(options.packages, Some(vec!["amssymb".to_string()]));
+    }
+
+    #[test]
+    fn test_results() {
+        let scores = || vec![score("unknown", 0.1), score(TEXTLESS, 0.2)];
+
+        let all = results(scores(), ClassifyOptions::default());
+        assert_eq!(all.len(), 2);
+        assert!(all[0].symbol.is_none());
+        assert_eq!(all[1].symbol.as_ref().unwrap().command, "\\textless");
+        assert_eq!(all[1].symbol.as_ref().unwrap().id, TEXTLESS);
+
+        let limited = results(
+            scores(),
+            ClassifyOptions {
+                limit: Some(1),
+                ..ClassifyOptions::default()
+            },
+        );
+        assert_eq!(limited.len(), 1);
+
+        let text = results(
+            scores(),
+            ClassifyOptions {
+                mode: Some(Mode::Text),
+                ..ClassifyOptions::default()
+            },
+        );
+        assert_eq!(text.len(), 1);
+        assert_eq!(text[0].id, TEXTLESS);
+
+        let math = results(
+            scores(),
+            ClassifyOptions {
+                mode: Some(Mode::Math),
+                ..ClassifyOptions::default()
+            },
+        );
+        assert!(math.is_empty());
+
+        let rejection = response(
+            scores(),
+            true,
+            ClassifyOptions {
+                reject: Some(true),
+                ..ClassifyOptions::default()
+            },
+        );
+        let value = serde_json::to_value(&rejection).unwrap();
+        assert_eq!(value["unknown"], true);
+        assert_eq!(value["results"].as_array().unwrap().len(), 2);
+        assert!(
+            serde_json::to_value(response(scores(), true, ClassifyOptions::default()))
+                .unwrap()
+                .is_array()
+        );
+    }
+}
@@ -0,0 +1,72 @@
+//! Request and response formats of the original Haskell backend, so its web frontend can be
+//! pointed at this server unmodified
+
+use crate::classify::ClassifyResult;
+use crate::symbols::SymbolInfo;
+use detexify::base32_to_base64;
+use serde::{Deserialize, Serialize};
+
+/// Form body of `POST /hs/classify`, `strokes` holds the strokes as a JSON string
+#[derive(Debug, Deserialize)]
+pub struct HsClassifyForm {
+    pub strokes: String,
+}
+
+/// Form body of `POST /hs/train`, `id` is the base64 symbol id and `strokes` holds the strokes
+/// as a JSON string
+#[derive(Debug, Deserialize)]
+pub struct HsTrainForm {
+    pub id: String,
+    pub strokes: String,
+}
+
+/// A symbol as described by the Haskell backend, which uses base64 ids
+#[derive(Debug, Serialize)]
+pub struct HsSymbol {
+    pub id: String,
+    pub command: &'static str,
+    pub package: &'static str,
+    pub fontenc: &'static str,
+    pub mathmode: bool,
+    pub textmode: bool,
+}
+
+impl From<SymbolInfo> for HsSymbol {
+    fn from(symbol: SymbolInfo) -> Self {
+        HsSymbol {
+            id: to_base64(symbol.id),
+            command: symbol.command,
+            package: symbol.package,
+            fontenc: symbol.font_encoding,
+            mathmode: symbol.math_mode,
+            textmode: symbol.text_mode,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct HsScore {
+    pub id: String,
+    pub score: f64,
+    pub symbol: HsSymbol,
+}
+
+/// The base64 encoding of a base32 id, ids that aren't base32 are kept as they are
+fn to_base64(id: String) -> String {
+    base32_to_base64(&id).unwrap_or(id)
+}
+
+/// Converts classification results, dropping ids that aren't in the symbol table as the
+/// frontend can't display them
+pub fn scores(results: Vec<ClassifyResult>) -> Vec<HsScore> {
+    results
+        .into_iter()
+        .filter_map(|result| {
+            Some(HsScore {
+                symbol: result.symbol?.into(),
+                id: to_base64(result.id),
+                score: result.score,
+            })
+        })
+        .collect()
+}
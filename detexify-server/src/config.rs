@@ -0,0 +1,379 @@
+use crate::rate_limit::RateLimiter;
+use crate::symbols;
+use crate::validation::Limits;
+use clap::Parser;
+use detexify::Symbol;
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::num::NonZeroU32;
+use std::path::{Path, PathBuf};
+
+/// Server configuration, read from an optional TOML file and overridden by command line flags
+/// or their environment variables
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub address: String,
+    pub port: u16,
+    /// Snapshot to start from instead of the built-in one
+    pub snapshot: Option<PathBuf>,
+    /// File restricting the server to a subset of symbols
+    pub symbols: Option<PathBuf>,
+    /// Maximum size of a request body or WebSocket message in bytes, except snapshot uploads
+    pub max_request_size: usize,
+    /// Maximum number of strokes in a sample
+    pub max_strokes: usize,
+    /// Maximum number of points in a stroke
+    pub max_points_per_stroke: usize,
+    /// Maximum number of points in a sample
+    pub max_points: usize,
+    /// Requests per second allowed from each client, unlimited when unset
+    pub rate_limit: Option<NonZeroU32>,
+    /// Requests a client may make in a burst, defaults to the rate limit
+    pub rate_limit_burst: Option<NonZeroU32>,
+    /// Softmax temperature turning scores into confidences, see `detexify calibrate`
+    pub temperature: Option<f64>,
+    /// Standard deviations a drawing's score may exceed the mean intra-class score of its best
+    /// match before `reject` reports it as unknown, rejection is disabled when unset
+    pub rejection_tolerance: Option<f64>,
+    /// Number of worker threads and of classifications running at once, defaults to the number
+    /// of CPUs
+    pub workers: Option<usize>,
+    /// Origins allowed to make cross-origin requests, `*` allows any
+    pub cors_origins: Vec<String>,
+    pub data_dir: Option<PathBuf>,
+    /// Dataset `POST /feedback` appends to, defaults to `feedback.jsonl` in the data directory
+    pub feedback_file: Option<PathBuf>,
+    /// Seconds between snapshots of the data directory
+    pub snapshot_interval: u64,
+    pub snapshot_token: Option<String>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        let limits = Limits::default();
+        Config {
+            address: "localhost".to_string(),
+            port: 8000,
+            snapshot: None,
+            symbols: None,
+            max_request_size: 2 * 1024 * 1024,
+            max_strokes: limits.strokes,
+            max_points_per_stroke: limits.points_per_stroke,
+            max_points: limits.points,
+            rate_limit: None,
+            rate_limit_burst: None,
+            temperature: None,
+            rejection_tolerance: None,
+            workers: None,
+            cors_origins: Vec::new(),
+            data_dir: None,
+            feedback_file: None,
+            snapshot_interval: 300,
+            snapshot_token: None,
+        }
+    }
+}
+
+#[derive(Debug, Parser)]
+#[command(version, about = "Rust port of the detexify backend")]
+pub struct Cli {
+    /// TOML configuration file
+    #[arg(short, long, env = "DETEXIFY_CONFIG")]
+    config: Option<PathBuf>,
+    #[arg(long, env = "DETEXIFY_ADDRESS")]
+    address: Option<String>,
+    #[arg(short, long, env = "DETEXIFY_PORT")]
+    port: Option<u16>,
+    /// Snapshot to start from instead of the built-in one
+    #[arg(long, env = "DETEXIFY_SNAPSHOT")]
+    snapshot: Option<PathBuf>,
+    /// File of symbol ids or commands, one per line, restricting the server to those symbols
+    #[arg(long, env = "DETEXIFY_SYMBOLS")]
+    symbols: Option<PathBuf>,
+    /// Maximum size of a request body in bytes
+    #[arg(long, env = "DETEXIFY_MAX_REQUEST_SIZE")]
+    max_request_size: Option<usize>,
+    /// Maximum number of strokes in a sample
+    #[arg(long, env = "DETEXIFY_MAX_STROKES")]
+    max_strokes: Option<usize>,
+    /// Maximum number of points in a stroke
+    #[arg(long, env = "DETEXIFY_MAX_POINTS_PER_STROKE")]
+    max_points_per_stroke: Option<usize>,
+    /// Maximum number of points in a sample
+    #[arg(long, env = "DETEXIFY_MAX_POINTS")]
+    max_points: Option<usize>,
+    /// Requests per second allowed from each client
+    #[arg(long, env = "DETEXIFY_RATE_LIMIT")]
+    rate_limit: Option<NonZeroU32>,
+    /// Requests a client may make in a burst
+    #[arg(long, env = "DETEXIFY_RATE_LIMIT_BURST")]
+    rate_limit_burst: Option<NonZeroU32>,
+    /// Softmax temperature turning scores into confidences, as printed by `detexify calibrate`
+    #[arg(long, env = "DETEXIFY_TEMPERATURE")]
+    temperature: Option<f64>,
+    /// Standard deviations above the mean intra-class score before a drawing is rejected
+    #[arg(long, env = "DETEXIFY_REJECTION_TOLERANCE")]
+    rejection_tolerance: Option<f64>,
+    /// Number of worker threads and classifications running at once
+    #[arg(long, env = "DETEXIFY_WORKERS")]
+    workers: Option<usize>,
+    /// Origin allowed to make cross-origin requests, may be repeated, `*` allows any
+    #[arg(
+        long = "cors-origin",
+        env = "DETEXIFY_CORS_ORIGINS",
+        value_delimiter = ','
+    )]
+    cors_origins: Vec<String>,
+    /// Directory to persist training data to
+    #[arg(long, env = "DETEXIFY_DATA_DIR")]
+    data_dir: Option<PathBuf>,
+    /// File to record feedback to, defaults to `feedback.jsonl` in the data directory
+    #[arg(long, env = "DETEXIFY_FEEDBACK_FILE")]
+    feedback_file: Option<PathBuf>,
+    /// Seconds between snapshots of the data directory
+    #[arg(long, env = "DETEXIFY_SNAPSHOT_INTERVAL")]
+    snapshot_interval: Option<u64>,
+    /// Token required by `PUT /snapshot`, which is disabled when unset
+    #[arg(long, env = "DETEXIFY_SNAPSHOT_TOKEN", hide_env_values = true)]
+    snapshot_token: Option<String>,
+}
+
+impl Cli {
+    /// Loads the configuration file, if any, and applies the flags on top of it
+    pub fn into_config(self) -> Result<Config, String> {
+        let mut config = match &self.config {
+            Some(path) => {
+                let contents = fs::read_to_string(path)
+                    .map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+                toml::from_str(&contents)
+                    .map_err(|e| format!("failed to parse {}: {}", path.display(), e))?
+            }
+            None => Config::default(),
+        };
+
+        if let Some(address) = self.address {
+            config.address = address;
+        }
+        if let Some(port) = self.port {
+            config.port = port;
+        }
+        if self.snapshot.is_some() {
+            config.snapshot = self.snapshot;
+        }
+        if self.symbols.is_some() {
+            config.symbols = self.symbols;
+        }
+        if let Some(max_request_size) = self.max_request_size {
+            config.max_request_size = max_request_size;
+        }
+        if let Some(max_strokes) = self.max_strokes {
+            config.max_strokes = max_strokes;
+        }
+        if let Some(max_points_per_stroke) = self.max_points_per_stroke {
+            config.max_points_per_stroke = max_points_per_stroke;
+        }
+        if let Some(max_points) = self.max_points {
+            config.max_points = max_points;
+        }
+        if self.rate_limit.is_some() {
+            config.rate_limit = self.rate_limit;
+        }
+        if self.rate_limit_burst.is_some() {
+            config.rate_limit_burst = self.rate_limit_burst;
+        }
+        if self.temperature.is_some() {
+            config.temperature = self.temperature;
+        }
+        if self.rejection_tolerance.is_some() {
+            config.rejection_tolerance = self.rejection_tolerance;
+        }
+        if self.workers.is_some() {
+            config.workers = self.workers;
+        }
+        if !self.cors_origins.is_empty() {
+            config.cors_origins = self.cors_origins;
+        }
+        if self.data_dir.is_some() {
+            config.data_dir = self.data_dir;
+        }
+        if self.feedback_file.is_some() {
+            config.feedback_file = self.feedback_file;
+        }
+        if let Some(snapshot_interval) = self.snapshot_interval {
+            config.snapshot_interval = snapshot_interval;
+        }
+        if self.snapshot_token.is_some() {
+            config.snapshot_token = self.snapshot_token;
+        }
+
+        if config.workers == Some(0) {
+            return Err("workers must be at least 1".to_string());
+        }
+        if config.snapshot_interval == 0 {
+            return Err("snapshot_interval must be at least 1 second".to_string());
+        }
+        if config
+            .temperature
+            .is_some_and(|t| !(t > 0.0 && t.is_finite()))
+        {
+            return Err("temperature must be a positive number".to_string());
+        }
+        if config
+            .rejection_tolerance
+            .is_some_and(|t| !(t >= 0.0 && t.is_finite()))
+        {
+            return Err("rejection_tolerance must be a non-negative number".to_string());
+        }
+
+        Ok(config)
+    }
+}
+
+impl Config {
+    pub fn limits(&self) -> Limits {
+        Limits {
+            strokes: self.max_strokes,
+            points_per_stroke: self.max_points_per_stroke,
+            points: self.max_points,
+        }
+    }
+
+    /// Path of the feedback dataset, if feedback is enabled
+    pub fn feedback_path(&self) -> Option<PathBuf> {
+        self.feedback_file
+            .clone()
+            .or_else(|| Some(self.data_dir.as_ref()?.join("feedback.jsonl")))
+    }
+
+    /// The per-client rate limiter, if a rate limit is set
+    pub fn rate_limiter(&self) -> Option<RateLimiter> {
+        self.rate_limit
+            .map(|rate| RateLimiter::new(rate, self.rate_limit_burst.unwrap_or(rate)))
+    }
+}
+
+/// Reads a symbol set file: one symbol id or command per line, blank lines and lines starting
+/// with `#` are ignored. A command selects the symbol in every package that defines it.
+pub fn load_symbol_set(path: &Path) -> io::Result<HashSet<String>> {
+    let mut ids = HashSet::new();
+
+    for line in fs::read_to_string(path)?.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        if line.starts_with('\\') {
+            let before = ids.len();
+            ids.extend(
+                symbols::table()
+                    .iter()
+                    .filter(|(_, s)| s.command == line)
+                    .map(|(id, _)| id.to_string()),
+            );
+            if ids.len() == before {
+                return Err(unknown_symbol(line));
+            }
+        } else if Symbol::from_id(line).is_some() {
+            ids.insert(line.to_string());
+        } else {
+            return Err(unknown_symbol(line));
+        }
+    }
+
+    Ok(ids)
+}
+
+fn unknown_symbol(symbol: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("unknown symbol {}", symbol),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_config() {
+        let dir = std::env::temp_dir().join(format!("detexify-config-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("config.toml");
+        fs::write(
+            &path,
+            "port = 3000\ncors_origins = [\"https://example.com\"]\nworkers = 2\nmax_strokes = 10\n",
+        )
+        .unwrap();
+
+        let cli = Cli::parse_from(vec![
+            "detexify-server",
+            "--config",
+            path.to_str().unwrap(),
+            "--port",
+            "4000",
+            "--rate-limit",
+            "5",
+        ]);
+        let config = cli.into_config().unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(
+            config,
+            Config {
+                port: 4000,
+                workers: Some(2),
+                max_strokes: 10,
+                rate_limit: NonZeroU32::new(5),
+                cors_origins: vec!["https://example.com".to_string()],
+                ..Config::default()
+            }
+        );
+    }
+
+    #[test]
+    fn test_invalid_config() {
+        let error = |flag: &str, value: &str| {
+            Cli::parse_from(vec!["detexify-server", flag, value])
+                .into_config()
+                .unwrap_err()
+        };
+
+        assert_eq!(error("--workers", "0"), "workers must be at least 1");
+        assert_eq!(
+            error("--snapshot-interval", "0"),
+            "snapshot_interval must be at least 1 second"
+        );
+        assert_eq!(
+            error("--temperature", "0"),
+            "temperature must be a positive number"
+        );
+        assert_eq!(
+            error("--rejection-tolerance", "NaN"),
+            "rejection_tolerance must be a non-negative number"
+        );
+    }
+
+    #[test]
+    fn test_symbol_set() {
+        let dir = std::env::temp_dir().join(format!("detexify-symbols-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("symbols.txt");
+        fs::write(
+            &path,
+            "# punctuation\n\\textless\n\nNRQXIZLYGJSS2T2UGEWV65DFPB2GEYLS\n",
+        )
+        .unwrap();
+        let ids = load_symbol_set(&path).unwrap();
+
+        fs::write(&path, "\\notacommand\n").unwrap();
+        let unknown = load_symbol_set(&path);
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert!(ids.contains("NRQXIZLYGJSS2T2UGEWV65DFPB2GYZLTOM"));
+        assert!(ids.contains("NRQXIZLYGJSS2T2UGEWV65DFPB2GEYLS"));
+        assert!(unknown.is_err());
+    }
+}
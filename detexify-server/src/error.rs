@@ -0,0 +1,115 @@
+use axum::extract::rejection::{FormRejection, JsonRejection, QueryRejection};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use serde::Serialize;
+use serde_json::json;
+
+/// Stable error codes returned in the body of every error response
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    BadRequest,
+    BadJson,
+    EmptySample,
+    InvalidCoordinates,
+    TooManyStrokes,
+    TooManyPoints,
+    TooManySamples,
+    UnknownSymbol,
+    UnknownClassifier,
+    ClassifierExists,
+    InvalidParameter,
+    InvalidSnapshot,
+    Unauthorized,
+    Forbidden,
+    NotFound,
+    MethodNotAllowed,
+    NotReady,
+    RateLimited,
+    Internal,
+}
+
+/// An error response with a JSON body of the form
+/// `{"error": {"code": "empty_sample", "message": "..."}}`
+#[derive(Debug)]
+pub struct ApiError {
+    pub status: StatusCode,
+    pub code: ErrorCode,
+    pub message: String,
+}
+
+impl ApiError {
+    pub fn new<M: Into<String>>(status: StatusCode, code: ErrorCode, message: M) -> Self {
+        ApiError {
+            status,
+            code,
+            message: message.into(),
+        }
+    }
+
+    pub fn internal<E: std::fmt::Display>(context: &str, error: E) -> Self {
+        ApiError::new(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            ErrorCode::Internal,
+            format!("{}: {}", context, error),
+        )
+    }
+}
+
+impl From<JsonRejection> for ApiError {
+    fn from(rejection: JsonRejection) -> Self {
+        // a body that doesn't have the expected shape is reported the same as a syntax error
+        let status = match rejection.status() {
+            StatusCode::UNPROCESSABLE_ENTITY => StatusCode::BAD_REQUEST,
+            status => status,
+        };
+
+        ApiError::new(status, ErrorCode::BadJson, rejection.body_text())
+    }
+}
+
+impl From<QueryRejection> for ApiError {
+    fn from(rejection: QueryRejection) -> Self {
+        ApiError::new(
+            StatusCode::BAD_REQUEST,
+            ErrorCode::InvalidParameter,
+            rejection.body_text(),
+        )
+    }
+}
+
+impl From<FormRejection> for ApiError {
+    fn from(rejection: FormRejection) -> Self {
+        ApiError::new(
+            rejection.status(),
+            ErrorCode::BadRequest,
+            rejection.body_text(),
+        )
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let body = Json(json!({
+            "error": {
+                "code": self.code,
+                "message": self.message,
+            }
+        }));
+
+        (self.status, body).into_response()
+    }
+}
+
+pub async fn not_found() -> ApiError {
+    ApiError::new(StatusCode::NOT_FOUND, ErrorCode::NotFound, "not found")
+}
+
+pub async fn method_not_allowed() -> ApiError {
+    ApiError::new(
+        StatusCode::METHOD_NOT_ALLOWED,
+        ErrorCode::MethodNotAllowed,
+        "method not allowed",
+    )
+}
@@ -0,0 +1,161 @@
+use detexify::{Classifier, Stroke, StrokeSample};
+use serde::{Deserialize, Serialize};
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Body of `POST /feedback`
+#[derive(Debug, Deserialize)]
+pub struct FeedbackRequest {
+    pub strokes: Vec<Stroke>,
+    pub ranking: Vec<String>,
+    pub chosen: String,
+}
+
+/// A symbol picked by a user from the results of classifying their drawing
+#[derive(Debug, Deserialize, Serialize)]
+pub struct FeedbackRecord {
+    /// Seconds since the unix epoch
+    pub timestamp: u64,
+    /// The drawing as sent by the client, before preprocessing
+    pub strokes: Vec<Stroke>,
+    /// Ids of the results shown to the user, best first
+    pub ranking: Vec<String>,
+    /// Id of the symbol the user picked
+    pub chosen: String,
+}
+
+impl FeedbackRecord {
+    pub fn new(strokes: Vec<Stroke>, ranking: Vec<String>, chosen: String) -> FeedbackRecord {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+
+        FeedbackRecord {
+            timestamp,
+            strokes,
+            ranking,
+            chosen,
+        }
+    }
+
+    /// Position of the chosen symbol in the ranking, `None` if the user picked a symbol that
+    /// wasn't suggested
+    pub fn rank(&self) -> Option<usize> {
+        self.ranking.iter().position(|id| *id == self.chosen)
+    }
+}
+
+/// Append-only dataset of feedback records, one JSON record per line
+pub struct FeedbackLog {
+    file: Mutex<File>,
+}
+
+impl FeedbackLog {
+    pub fn open(path: &Path) -> io::Result<FeedbackLog> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(FeedbackLog {
+            file: Mutex::new(file),
+        })
+    }
+
+    pub fn append(&self, record: &FeedbackRecord) -> io::Result<()> {
+        // a single write per record so concurrent appends can't interleave
+        let line = format!("{}\n", serde_json::to_string(record)?);
+        self.file.lock().unwrap().write_all(line.as_bytes())
+    }
+}
+
+/// Summary of a feedback export
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub struct ExportStats {
+    /// Parsed records
+    pub records: usize,
+    /// Records trained into the classifier
+    pub exported: usize,
+    /// Unparseable lines and records without points
+    pub skipped: usize,
+    /// Records where the chosen symbol was the first suggestion
+    pub top1: usize,
+    /// Records where the chosen symbol wasn't suggested at all
+    pub unranked: usize,
+}
+
+/// Trains `classifier` on every record of a feedback dataset
+pub fn export<R: BufRead>(reader: R, classifier: &mut Classifier) -> io::Result<ExportStats> {
+    let mut stats = ExportStats::default();
+
+    for line in reader.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let record: FeedbackRecord = match serde_json::from_str(&line) {
+            Ok(record) => record,
+            Err(_) => {
+                stats.skipped += 1;
+                continue;
+            }
+        };
+
+        stats.records += 1;
+        match record.rank() {
+            Some(0) => stats.top1 += 1,
+            Some(_) => {}
+            None => stats.unranked += 1,
+        }
+
+        match StrokeSample::new(record.strokes) {
+            Some(sample) => {
+                classifier.train(record.chosen, sample);
+                stats.exported += 1;
+            }
+            None => stats.skipped += 1,
+        }
+    }
+
+    Ok(stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use detexify::Point;
+
+    #[test]
+    fn test_export() {
+        let stroke = Stroke::new(vec![Point { x: 0.0, y: 0.0 }, Point { x: 1.0, y: 1.0 }]);
+        let ranking = vec!["a".to_string(), "b".to_string()];
+        let records = [
+            FeedbackRecord::new(vec![stroke.clone()], ranking.clone(), "a".to_string()),
+            FeedbackRecord::new(vec![stroke], ranking.clone(), "b".to_string()),
+            FeedbackRecord::new(vec![], ranking, "c".to_string()),
+        ];
+
+        let mut dataset = String::new();
+        for record in &records {
+            dataset.push_str(&serde_json::to_string(record).unwrap());
+            dataset.push('\n');
+        }
+        dataset.push_str("{\"truncated\n");
+
+        let mut classifier = Classifier::new();
+        let stats = export(dataset.as_bytes(), &mut classifier).unwrap();
+
+        assert_eq!(
+            stats,
+            ExportStats {
+                records: 3,
+                exported: 2,
+                skipped: 2,
+                top1: 1,
+                unranked: 1,
+            }
+        );
+        assert_eq!(classifier.symbol_count(), 2);
+    }
+}
@@ -0,0 +1,177 @@
+pub mod classify;
+pub mod compat;
+pub mod config;
+pub mod error;
+pub mod feedback;
+pub mod live;
+pub mod metrics;
+pub mod openapi;
+pub mod rate_limit;
+mod routes;
+pub mod store;
+pub mod symbols;
+pub mod tenants;
+pub mod validation;
+pub mod workers;
+
+use axum::extract::DefaultBodyLimit;
+use axum::handler::Handler;
+use axum::http::Method;
+use axum::middleware;
+use axum::routing::{on, MethodFilter, MethodRouter};
+use axum::Router;
+use feedback::FeedbackLog;
+use metrics::Metrics;
+use rate_limit::RateLimiter;
+use std::collections::{BTreeMap, HashSet};
+use std::convert::TryFrom;
+use std::sync::Arc;
+use store::Store;
+use tenants::Tenants;
+use validation::Limits;
+use workers::Workers;
+
+/// Maximum size of an uploaded snapshot in bytes
+pub const MAX_SNAPSHOT_SIZE: usize = 256 * 1024 * 1024;
+
+pub struct AppState {
+    pub store: Arc<Store>,
+    /// Token required to replace the snapshot, `PUT /snapshot` is disabled when unset
+    pub snapshot_token: Option<String>,
+    /// Ids of the symbols the server is restricted to, all symbols when unset
+    pub symbols: Option<HashSet<String>>,
+    pub metrics: Metrics,
+    pub limits: Limits,
+    /// Maximum size of a request body or WebSocket message in bytes, except snapshot uploads
+    pub max_request_size: usize,
+    /// Per-client rate limit of every route but the health and metrics ones
+    pub rate_limiter: Option<RateLimiter>,
+    /// Dataset `POST /feedback` appends to, the route is disabled when unset
+    pub feedback: Option<FeedbackLog>,
+    /// Named classifiers layered on top of `store`
+    pub tenants: Tenants,
+    /// Bounds the classifications running at once
+    pub workers: Workers,
+}
+
+impl AppState {
+    /// Whether the symbol `id` is in the server's symbol set
+    pub fn allows(&self, id: &str) -> bool {
+        match &self.symbols {
+            Some(symbols) => symbols.contains(id),
+            None => true,
+        }
+    }
+}
+
+/// A route of the server, `app` serves and `openapi::document` documents exactly these
+pub(crate) struct Route {
+    pub(crate) path: &'static str,
+    pub(crate) method: Method,
+    /// Whether requests count against the rate limit
+    pub(crate) limited: bool,
+    handler: MethodRouter<Arc<AppState>>,
+}
+
+impl Route {
+    fn new<H, T>(method: Method, path: &'static str, handler: H) -> Route
+    where
+        H: Handler<T, Arc<AppState>>,
+        T: 'static,
+    {
+        let filter = MethodFilter::try_from(method.clone()).unwrap();
+        Route {
+            path,
+            method,
+            limited: true,
+            handler: on(filter, handler),
+        }
+    }
+
+    /// Exempts the route from the rate limit
+    fn unlimited(self) -> Route {
+        Route {
+            limited: false,
+            ..self
+        }
+    }
+
+    /// Raises the request body limit of the route to `limit` bytes
+    fn body_limit(self, limit: usize) -> Route {
+        Route {
+            handler: self.handler.layer(DefaultBodyLimit::max(limit)),
+            ..self
+        }
+    }
+}
+
+/// Every route of the server but the fallbacks
+pub(crate) fn all_routes() -> Vec<Route> {
+    vec![
+        Route::new(Method::GET, "/healthz", routes::get_healthz).unlimited(),
+        Route::new(Method::GET, "/readyz", routes::get_readyz).unlimited(),
+        Route::new(Method::GET, "/metrics", routes::get_metrics).unlimited(),
+        Route::new(Method::GET, "/openapi.json", routes::get_openapi).unlimited(),
+        Route::new(Method::GET, "/", routes::get_root),
+        Route::new(Method::POST, "/classify", routes::post_classify),
+        Route::new(Method::POST, "/classify/batch", routes::post_classify_batch),
+        Route::new(Method::GET, "/classify/ws", routes::get_classify_ws),
+        Route::new(Method::POST, "/train/{id}", routes::post_train),
+        Route::new(Method::POST, "/feedback", routes::post_feedback),
+        Route::new(Method::POST, "/hs/classify", routes::post_hs_classify),
+        Route::new(Method::POST, "/hs/train", routes::post_hs_train),
+        Route::new(Method::GET, "/symbols", routes::get_symbols),
+        Route::new(Method::GET, "/snapshot", routes::get_snapshot),
+        Route::new(Method::PUT, "/snapshot", routes::put_snapshot).body_limit(MAX_SNAPSHOT_SIZE),
+        Route::new(Method::GET, "/c", routes::get_classifiers),
+        Route::new(Method::GET, "/c/{name}", routes::get_classifier),
+        Route::new(Method::PUT, "/c/{name}", routes::put_classifier),
+        Route::new(Method::DELETE, "/c/{name}", routes::delete_classifier),
+        Route::new(
+            Method::POST,
+            "/c/{name}/classify",
+            routes::post_tenant_classify,
+        ),
+        Route::new(
+            Method::POST,
+            "/c/{name}/train/{id}",
+            routes::post_tenant_train,
+        ),
+    ]
+}
+
+/// Builds the router serving every route of the server
+pub fn app(state: Arc<AppState>) -> Router {
+    // methods of the same path are merged into one route
+    let mut handlers: BTreeMap<(bool, &str), MethodRouter<Arc<AppState>>> = BTreeMap::new();
+    for route in all_routes() {
+        let handler = match handlers.remove(&(route.limited, route.path)) {
+            Some(handler) => handler.merge(route.handler),
+            None => route.handler,
+        };
+        handlers.insert((route.limited, route.path), handler);
+    }
+
+    let (mut limited, mut unlimited) = (Router::new(), Router::new());
+    for ((is_limited, path), handler) in handlers {
+        if is_limited {
+            limited = limited.route(path, handler);
+        } else {
+            unlimited = unlimited.route(path, handler);
+        }
+    }
+    let limited = limited.route_layer(middleware::from_fn_with_state(
+        state.clone(),
+        rate_limit::limit,
+    ));
+
+    unlimited
+        .merge(limited)
+        .fallback(error::not_found)
+        .method_not_allowed_fallback(error::method_not_allowed)
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            metrics::track,
+        ))
+        .with_state(state)
+}
@@ -0,0 +1,261 @@
+//! Incremental classification over a WebSocket at `/classify/ws`.
+//!
+//! The client streams the points of the drawing as they're drawn and sends a pen-up after every
+//! stroke, the server then replies with the results for all strokes drawn so far:
+//!
+//! ```text
+//! > {"type": "point", "x": 10, "y": 12}
+//! > {"type": "points", "points": [{"x": 11, "y": 12}, {"x": 13, "y": 14}]}
+//! > {"type": "pen_up"}
+//! < {"type": "results", "strokes": 1, "results": [{"id": "...", "score": ..., ...}]}
+//! > {"type": "clear"}
+//! ```
+//!
+//! Invalid messages are answered with `{"type": "error", "error": {"code": ..., "message": ...}}`
+//! and leave the drawing unchanged. Every pen-up counts against the client's rate limit, pen-ups
+//! over it are answered with a `rate_limited` error but still complete the stroke.
+
+use crate::classify::{ClassifyOptions, ClassifyResponse, ClassifyResult};
+use crate::error::{ApiError, ErrorCode};
+use crate::rate_limit::rate_limited;
+use crate::routes::{blocking, classify_sample};
+use crate::validation::{
+    check_point, check_point_count, check_stroke_count, check_stroke_points, sample_from_strokes,
+    Limits,
+};
+use crate::AppState;
+use axum::extract::ws::{Message, WebSocket};
+use axum::http::StatusCode;
+use detexify::{Point, Stroke, StrokeSample};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::net::IpAddr;
+use std::sync::Arc;
+
+/// Results per reply when the query doesn't set a limit, as every pen-up is answered with results
+pub const DEFAULT_LIMIT: usize = 10;
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ClientMessage {
+    /// Extends the current stroke
+    Point(Point),
+    /// Extends the current stroke by several points
+    Points { points: Vec<Point> },
+    /// Ends the current stroke and requests results
+    PenUp,
+    /// Starts a new drawing
+    Clear,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ServerMessage {
+    Results {
+        /// Number of strokes classified
+        strokes: usize,
+        /// Whether the drawing was rejected as not a symbol, only set when `reject` is
+        #[serde(skip_serializing_if = "Option::is_none")]
+        unknown: Option<bool>,
+        results: Vec<ClassifyResult>,
+    },
+}
+
+/// The drawing of a connection: its completed strokes and the stroke being drawn
+#[derive(Debug)]
+pub struct Drawing {
+    strokes: Vec<Stroke>,
+    current: Stroke,
+    points: usize,
+    limits: Limits,
+}
+
+impl Drawing {
+    pub fn new(limits: Limits) -> Drawing {
+        Drawing {
+            strokes: Vec::new(),
+            current: Stroke::default(),
+            points: 0,
+            limits,
+        }
+    }
+
+    /// Adds `points` to the current stroke, or none of them if any is invalid
+    pub fn add_points(&mut self, points: &[Point]) -> Result<(), ApiError> {
+        let current = self.current.points().count();
+        if current == 0 {
+            check_stroke_count(self.strokes.len() + 1, &self.limits)?;
+        }
+        check_stroke_points(current + points.len(), &self.limits)?;
+        check_point_count(self.points + points.len(), &self.limits)?;
+        points.iter().try_for_each(check_point)?;
+
+        for point in points {
+            self.current.add_point(*point);
+        }
+        self.points += points.len();
+
+        Ok(())
+    }
+
+    /// Completes the current stroke, returns `false` if it has no points
+    pub fn pen_up(&mut self) -> bool {
+        if self.current.points().next().is_none() {
+            return false;
+        }
+
+        self.strokes.push(std::mem::take(&mut self.current));
+        true
+    }
+
+    pub fn clear(&mut self) {
+        *self = Drawing::new(self.limits);
+    }
+
+    /// Number of completed strokes
+    pub fn strokes(&self) -> usize {
+        self.strokes.len()
+    }
+
+    /// Sample of the completed strokes
+    pub fn sample(&self) -> Result<StrokeSample, ApiError> {
+        sample_from_strokes(self.strokes.clone(), &self.limits)
+    }
+}
+
+/// Serves a WebSocket connection of `client` until it disconnects
+pub(crate) async fn run(
+    mut socket: WebSocket,
+    state: Arc<AppState>,
+    options: ClassifyOptions,
+    client: Option<IpAddr>,
+) {
+    let mut drawing = Drawing::new(state.limits);
+
+    while let Some(Ok(message)) = socket.recv().await {
+        let text = match message {
+            Message::Text(text) => text,
+            Message::Close(_) => break,
+            // pings are answered by axum
+            _ => continue,
+        };
+
+        let reply = match serde_json::from_str(text.as_str()) {
+            Ok(message) => handle(&mut drawing, message, &state, &options, client).await,
+            Err(e) => Err(ApiError::new(
+                StatusCode::BAD_REQUEST,
+                ErrorCode::BadJson,
+                format!("invalid message: {}", e),
+            )),
+        };
+
+        let reply = match reply {
+            Ok(Some(reply)) => serde_json::to_string(&reply).unwrap(),
+            Ok(None) => continue,
+            Err(e) => json!({
+                "type": "error",
+                "error": { "code": e.code, "message": e.message },
+            })
+            .to_string(),
+        };
+
+        if socket.send(Message::Text(reply.into())).await.is_err() {
+            break;
+        }
+    }
+}
+
+async fn handle(
+    drawing: &mut Drawing,
+    message: ClientMessage,
+    state: &Arc<AppState>,
+    options: &ClassifyOptions,
+    client: Option<IpAddr>,
+) -> Result<Option<ServerMessage>, ApiError> {
+    match message {
+        ClientMessage::Point(point) => drawing.add_points(&[point])?,
+        ClientMessage::Points { points } => drawing.add_points(&points)?,
+        ClientMessage::Clear => drawing.clear(),
+        ClientMessage::PenUp => {
+            if !drawing.pen_up() {
+                return Ok(None);
+            }
+            if let (Some(limiter), Some(client)) = (&state.rate_limiter, client) {
+                limiter.check(client).map_err(|_| rate_limited())?;
+            }
+
+            let sample = drawing.sample()?;
+            let (state, options) = (state.clone(), options.clone());
+            let response = blocking(state.workers.clone(), move || {
+                Ok(classify_sample(
+                    &state,
+                    None,
+                    sample,
+                    options,
+                    "/classify/ws",
+                ))
+            })
+            .await?;
+
+            let (unknown, results) = match response {
+                ClassifyResponse::Results(results) => (None, results),
+                ClassifyResponse::Rejection { unknown, results } => (Some(unknown), results),
+            };
+            return Ok(Some(ServerMessage::Results {
+                strokes: drawing.strokes(),
+                unknown,
+                results,
+            }));
+        }
+    }
+
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_drawing() {
+        let mut drawing = Drawing::new(Limits {
+            strokes: 1,
+            ..Limits::default()
+        });
+        assert!(!drawing.pen_up());
+
+        let message: ClientMessage = serde_json::from_str(
+            r#"{"type": "points", "points": [{"x": 0, "y": 0}, {"x": 1, "y": 1}]}"#,
+        )
+        .unwrap();
+        match message {
+            ClientMessage::Points { points } => drawing.add_points(&points).unwrap(),
+            _ => panic!("expected points"),
+        }
+        assert!(drawing.pen_up());
+        assert_eq!(drawing.strokes(), 1);
+        assert!(drawing.sample().is_ok());
+
+        assert_eq!(
+            drawing
+                .add_points(&[Point { x: 0.0, y: 0.0 }])
+                .unwrap_err()
+                .code,
+            ErrorCode::TooManyStrokes
+        );
+
+        drawing.clear();
+        assert_eq!(drawing.strokes(), 0);
+        assert_eq!(
+            drawing
+                .add_points(&[Point {
+                    x: f64::NAN,
+                    y: 0.0
+                }])
+                .unwrap_err()
+                .code,
+            ErrorCode::InvalidCoordinates
+        );
+        assert!(!drawing.pen_up());
+    }
+}
@@ -1,38 +1,175 @@
-#![feature(plugin)]
-#![feature(decl_macro)]
-#![feature(type_ascription)]
-
-#[macro_use]
-extern crate rocket;
-#[macro_use]
-extern crate rocket_contrib;
-
-use detexify::{Classifier, StrokeSample};
-use rocket::State;
-use rocket_contrib::json::{Json, JsonValue};
-
-#[get("/")]
-fn get_root() -> JsonValue {
-    json!({
-        "server": "rust detexify server",
-        "version": "0.2.0",
-    })
+use axum::extract::DefaultBodyLimit;
+use axum::http::HeaderValue;
+use clap::Parser;
+use detexify::Classifier;
+use detexify_server::config::{load_symbol_set, Cli, Config};
+use detexify_server::feedback::FeedbackLog;
+use detexify_server::metrics::Metrics;
+use detexify_server::store::Store;
+use detexify_server::tenants::Tenants;
+use detexify_server::workers::Workers;
+use detexify_server::{app, AppState};
+use std::fs::File;
+use std::io::{self, BufReader};
+use std::net::SocketAddr;
+use std::process;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use tower_http::cors::{AllowOrigin, Any, CorsLayer};
+
+fn fail(message: String) -> ! {
+    eprintln!("{}", message);
+    process::exit(1)
+}
+
+/// The configured snapshot, or the built-in one
+fn initial_classifier(config: &Config) -> io::Result<Classifier> {
+    match &config.snapshot {
+        Some(path) => Ok(Classifier::from_snapshot(BufReader::new(File::open(
+            path,
+        )?))?),
+        None => Ok(Classifier::default()),
+    }
 }
 
-#[post("/classify", format = "json", data = "<stroke_sample>")]
-fn post_classify(
-    stroke_sample: Json<Vec<detexify::Stroke>>,
-    classifier: State<Classifier>,
-) -> Option<Json<Vec<detexify::Score>>> {
-    let sample = StrokeSample::new(stroke_sample.0)?;
-    classifier.classify(sample).map(Json)
+fn cors(origins: &[String]) -> CorsLayer {
+    let origins = if origins.iter().any(|o| o == "*") {
+        AllowOrigin::from(Any)
+    } else {
+        AllowOrigin::list(origins.iter().map(|o| {
+            HeaderValue::from_str(o).unwrap_or_else(|_| fail(format!("invalid CORS origin {}", o)))
+        }))
+    };
+
+    CorsLayer::new()
+        .allow_origin(origins)
+        .allow_methods(Any)
+        .allow_headers(Any)
 }
 
 fn main() {
-    let classifier = Classifier::default();
+    let config = Cli::parse().into_config().unwrap_or_else(|e| fail(e));
+
+    let mut runtime = tokio::runtime::Builder::new_multi_thread();
+    if let Some(workers) = config.workers {
+        runtime.worker_threads(workers);
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(workers)
+            .build_global()
+            .unwrap();
+    }
+    let runtime = runtime.enable_all().build().unwrap();
+
+    runtime.block_on(serve(config));
+}
+
+async fn serve(config: Config) {
+    let (store, tenants) = match &config.data_dir {
+        Some(dir) => {
+            let open_failed = |e: io::Error| -> String {
+                format!("failed to open data directory {}: {}", dir.display(), e)
+            };
+            let store = Store::open(dir.clone(), || initial_classifier(&config))
+                .unwrap_or_else(|e| fail(open_failed(e)));
+            let tenants =
+                Tenants::open(dir.join("tenants")).unwrap_or_else(|e| fail(open_failed(e)));
+
+            (store, tenants)
+        }
+        None => {
+            let classifier = initial_classifier(&config)
+                .unwrap_or_else(|e| fail(format!("failed to load snapshot: {}", e)));
+            (Store::in_memory(classifier), Tenants::in_memory())
+        }
+    };
+    if let Some(tolerance) = config.rejection_tolerance {
+        store.learn_thresholds(tolerance);
+    }
+    let tenants = match config.temperature {
+        Some(temperature) => {
+            store.set_temperature(temperature);
+            tenants.with_temperature(temperature)
+        }
+        None => tenants,
+    };
+    let store = Arc::new(store);
+
+    let symbols = config.symbols.as_ref().map(|path| {
+        load_symbol_set(path)
+            .unwrap_or_else(|e| fail(format!("failed to read {}: {}", path.display(), e)))
+    });
+
+    let feedback = config.feedback_path().map(|path| {
+        FeedbackLog::open(&path)
+            .unwrap_or_else(|e| fail(format!("failed to open {}: {}", path.display(), e)))
+    });
+
+    let state = Arc::new(AppState {
+        store,
+        snapshot_token: config.snapshot_token.clone(),
+        symbols,
+        metrics: Metrics::new(),
+        limits: config.limits(),
+        max_request_size: config.max_request_size,
+        rate_limiter: config.rate_limiter(),
+        feedback,
+        tenants,
+        workers: config.workers.map_or_else(Workers::default, Workers::new),
+    });
+
+    if config.data_dir.is_some() {
+        let interval = config.snapshot_interval;
+        let state = state.clone();
+        thread::spawn(move || loop {
+            thread::sleep(Duration::from_secs(interval));
+            snapshot(&state);
+        });
+    }
+
+    if state.rate_limiter.is_some() {
+        let state = state.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(60));
+            loop {
+                interval.tick().await;
+                if let Some(limiter) = &state.rate_limiter {
+                    limiter.retain_recent();
+                }
+            }
+        });
+    }
+
+    let mut router = app(state.clone()).layer(DefaultBodyLimit::max(config.max_request_size));
+    if !config.cors_origins.is_empty() {
+        router = router.layer(cors(&config.cors_origins));
+    }
+
+    let address = (config.address.as_str(), config.port);
+    let listener = tokio::net::TcpListener::bind(address)
+        .await
+        .unwrap_or_else(|e| fail(format!("failed to bind {}:{}: {}", address.0, address.1, e)));
+    println!("listening on http://{}", listener.local_addr().unwrap());
+
+    axum::serve(
+        listener,
+        router.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .with_graceful_shutdown(async {
+        tokio::signal::ctrl_c().await.ok();
+    })
+    .await
+    .unwrap();
+
+    // don't lose samples trained since the last periodic snapshot
+    snapshot(&state);
+}
 
-    rocket::ignite()
-        .mount("/", routes![get_root, post_classify])
-        .manage(classifier)
-        .launch();
+fn snapshot(state: &AppState) {
+    if let Err(e) = state.store.snapshot() {
+        eprintln!("failed to write snapshot: {}", e);
+    }
+    if let Err(e) = state.tenants.snapshot() {
+        eprintln!("failed to write classifier snapshots: {}", e);
+    }
 }
@@ -0,0 +1,164 @@
+use crate::AppState;
+use axum::extract::{MatchedPath, Request, State};
+use axum::middleware::Next;
+use axum::response::Response;
+use detexify::Classifier;
+use prometheus::{
+    Encoder, Histogram, HistogramOpts, HistogramVec, IntCounterVec, IntGauge, Opts, Registry,
+    TextEncoder,
+};
+use std::sync::Arc;
+use std::time::Instant;
+
+/// Prometheus metrics of a server, kept in their own registry so several servers can run in one
+/// process
+pub struct Metrics {
+    registry: Registry,
+    requests: IntCounterVec,
+    request_duration: HistogramVec,
+    classify_duration: HistogramVec,
+    classify_samples: Histogram,
+    symbols: IntGauge,
+    samples: IntGauge,
+}
+
+impl Metrics {
+    pub fn new() -> Metrics {
+        let requests = IntCounterVec::new(
+            Opts::new("detexify_http_requests_total", "HTTP requests handled"),
+            &["method", "route", "status"],
+        )
+        .unwrap();
+        let request_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "detexify_http_request_duration_seconds",
+                "Time to handle an HTTP request",
+            ),
+            &["method", "route"],
+        )
+        .unwrap();
+        let classify_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "detexify_classify_duration_seconds",
+                "Time spent classifying the samples of a request",
+            )
+            .buckets(vec![
+                0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0,
+            ]),
+            &["route"],
+        )
+        .unwrap();
+        let classify_samples = Histogram::with_opts(
+            HistogramOpts::new(
+                "detexify_classify_samples",
+                "Number of samples classified per request",
+            )
+            .buckets(vec![
+                1.0, 2.0, 5.0, 10.0, 25.0, 50.0, 100.0, 250.0, 500.0, 1000.0,
+            ]),
+        )
+        .unwrap();
+        let symbols = IntGauge::new(
+            "detexify_classifier_symbols",
+            "Symbols known to the classifier",
+        )
+        .unwrap();
+        let samples = IntGauge::new(
+            "detexify_classifier_samples",
+            "Training samples held by the classifier",
+        )
+        .unwrap();
+
+        let registry = Registry::new();
+        registry.register(Box::new(requests.clone())).unwrap();
+        registry
+            .register(Box::new(request_duration.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(classify_duration.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(classify_samples.clone()))
+            .unwrap();
+        registry.register(Box::new(symbols.clone())).unwrap();
+        registry.register(Box::new(samples.clone())).unwrap();
+
+        Metrics {
+            registry,
+            requests,
+            request_duration,
+            classify_duration,
+            classify_samples,
+            symbols,
+            samples,
+        }
+    }
+
+    /// Records classifying `samples` samples for `route`, which took `start.elapsed()`
+    pub fn observe_classify(&self, route: &str, samples: usize, start: Instant) {
+        self.classify_duration
+            .with_label_values(&[route])
+            .observe(start.elapsed().as_secs_f64());
+        self.classify_samples.observe(samples as f64);
+    }
+
+    /// Renders every metric in the Prometheus text format, sizing the classifier first
+    pub fn render(&self, classifier: &Classifier) -> String {
+        self.symbols.set(classifier.symbol_count() as i64);
+        self.samples.set(classifier.sample_count() as i64);
+
+        let mut buffer = Vec::new();
+        TextEncoder::new()
+            .encode(&self.registry.gather(), &mut buffer)
+            .unwrap();
+        String::from_utf8(buffer).unwrap()
+    }
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Metrics::new()
+    }
+}
+
+/// Middleware counting requests and timing them by route
+pub async fn track(State(state): State<Arc<AppState>>, request: Request, next: Next) -> Response {
+    // label by route template so ids in paths don't create a series each
+    let route = match request.extensions().get::<MatchedPath>() {
+        Some(path) => path.as_str().to_string(),
+        None => "unmatched".to_string(),
+    };
+    let method = request.method().to_string();
+
+    let start = Instant::now();
+    let response = next.run(request).await;
+
+    state
+        .metrics
+        .request_duration
+        .with_label_values(&[&method, &route])
+        .observe(start.elapsed().as_secs_f64());
+    state
+        .metrics
+        .requests
+        .with_label_values(&[&method, &route, response.status().as_str()])
+        .inc();
+
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let metrics = Metrics::new();
+        metrics.observe_classify("/classify", 3, Instant::now());
+        let text = metrics.render(&Classifier::new());
+
+        assert!(text.contains("detexify_classify_samples_sum 3"));
+        assert!(text.contains("detexify_classify_duration_seconds_count{route=\"/classify\"} 1"));
+        assert!(text.contains("detexify_classifier_samples 0"));
+    }
+}
@@ -0,0 +1,564 @@
+//! OpenAPI description of the server, served at `/openapi.json`
+
+use crate::{all_routes, live};
+use serde_json::{json, Map, Value};
+
+fn schema(name: &str) -> Value {
+    json!({ "$ref": format!("#/components/schemas/{}", name) })
+}
+
+fn json_content(schema: Value) -> Value {
+    json!({ "application/json": { "schema": schema } })
+}
+
+fn body(schema: Value) -> Value {
+    json!({ "required": true, "content": json_content(schema) })
+}
+
+fn ok(description: &str, schema: Value) -> Value {
+    json!({ "description": description, "content": json_content(schema) })
+}
+
+fn error(description: &str) -> Value {
+    ok(description, self::schema("Error"))
+}
+
+fn path_parameter(name: &str, description: &str) -> Value {
+    json!({
+        "name": name,
+        "in": "path",
+        "required": true,
+        "description": description,
+        "schema": { "type": "string" },
+    })
+}
+
+fn query_parameter(name: &str, description: &str, schema: Value) -> Value {
+    json!({ "name": name, "in": "query", "description": description, "schema": schema })
+}
+
+fn classify_parameters() -> Vec<Value> {
+    vec![
+        query_parameter(
+            "limit",
+            "Maximum number of results",
+            json!({ "type": "integer", "minimum": 0 }),
+        ),
+        query_parameter(
+            "mode",
+            "Only return symbols usable in this mode",
+            schema("Mode"),
+        ),
+        query_parameter(
+            "packages",
+            "Only return symbols from these comma separated packages",
+            json!({ "type": "string" }),
+        ),
+        query_parameter(
+            "reject",
+            "Report whether the sample was rejected as not a symbol",
+            json!({ "type": "boolean" }),
+        ),
+    ]
+}
+
+fn classify_operation(summary: &str, mut parameters: Vec<Value>) -> Value {
+    parameters.extend(classify_parameters());
+    json!({
+        "summary": summary,
+        "parameters": parameters,
+        "requestBody": body(schema("ClassifyRequest")),
+        "responses": {
+            "200": ok("Matching symbols, best first", schema("ClassifyResponse")),
+            "400": error("Invalid JSON or query parameter"),
+            "403": error("`reject` is set but no rejection tolerance is"),
+            "413": error("Too many strokes or points"),
+            "422": error("Empty sample or invalid coordinates"),
+        },
+    })
+}
+
+fn train_operation(summary: &str, mut parameters: Vec<Value>) -> Value {
+    parameters.push(path_parameter("id", "Symbol id"));
+    json!({
+        "summary": summary,
+        "parameters": parameters,
+        "requestBody": body(schema("Sample")),
+        "responses": {
+            "200": ok("Number of samples the symbol now has", schema("TrainResult")),
+            "404": error("Unknown symbol"),
+            "413": error("Too many strokes or points"),
+            "422": error("Empty sample or invalid coordinates"),
+        },
+    })
+}
+
+fn components() -> Value {
+    json!({
+        "schemas": {
+            "Point": {
+                "type": "object",
+                "required": ["x", "y"],
+                "properties": {
+                    "x": { "type": "number" },
+                    "y": { "type": "number" },
+                },
+            },
+            "Stroke": {
+                "type": "array",
+                "description": "Points in the order they were drawn",
+                "items": schema("Point"),
+            },
+            "Sample": {
+                "type": "array",
+                "description": "Strokes of a drawing",
+                "items": schema("Stroke"),
+            },
+            "Mode": { "type": "string", "enum": ["math", "text"] },
+            "ClassifyOptions": {
+                "type": "object",
+                "properties": {
+                    "limit": { "type": "integer", "minimum": 0 },
+                    "mode": schema("Mode"),
+                    "packages": { "type": "array", "items": { "type": "string" } },
+                    "reject": { "type": "boolean" },
+                },
+            },
+            "ClassifyResponse": {
+                "oneOf": [
+                    { "type": "array", "items": schema("Score") },
+                    {
+                        "type": "object",
+                        "description": "Returned when `reject` is set",
+                        "required": ["unknown", "results"],
+                        "properties": {
+                            "unknown": {
+                                "type": "boolean",
+                                "description": "Whether the best match exceeds its symbol's rejection threshold",
+                            },
+                            "results": { "type": "array", "items": schema("Score") },
+                        },
+                    },
+                ],
+            },
+            "ClassifyRequest": {
+                "oneOf": [
+                    schema("Sample"),
+                    {
+                        "allOf": [
+                            {
+                                "type": "object",
+                                "required": ["strokes"],
+                                "properties": { "strokes": schema("Sample") },
+                            },
+                            schema("ClassifyOptions"),
+                        ],
+                    },
+                ],
+            },
+            "Symbol": {
+                "type": "object",
+                "required": ["id", "command", "package", "font_encoding", "text_mode", "math_mode"],
+                "properties": {
+                    "id": { "type": "string" },
+                    "command": { "type": "string" },
+                    "package": { "type": "string" },
+                    "font_encoding": { "type": "string" },
+                    "text_mode": { "type": "boolean" },
+                    "math_mode": { "type": "boolean" },
+                },
+            },
+            "Score": {
+                "type": "object",
+                "required": ["id", "score", "confidence", "symbol"],
+                "properties": {
+                    "id": { "type": "string" },
+                    "score": { "type": "number", "description": "Distance to the symbol, lower is better" },
+                    "confidence": { "type": "number", "minimum": 0, "maximum": 1 },
+                    "symbol": {
+                        "description": "`null` if the id isn't in the symbol table",
+                        "nullable": true,
+                        "allOf": [schema("Symbol")],
+                    },
+                },
+            },
+            "HsScore": {
+                "type": "object",
+                "required": ["id", "score", "symbol"],
+                "properties": {
+                    "id": { "type": "string", "description": "Base64 symbol id" },
+                    "score": { "type": "number" },
+                    "symbol": {
+                        "type": "object",
+                        "required": ["id", "command", "package", "fontenc", "mathmode", "textmode"],
+                        "properties": {
+                            "id": { "type": "string", "description": "Base64 symbol id" },
+                            "command": { "type": "string" },
+                            "package": { "type": "string" },
+                            "fontenc": { "type": "string" },
+                            "mathmode": { "type": "boolean" },
+                            "textmode": { "type": "boolean" },
+                        },
+                    },
+                },
+            },
+            "TrainResult": {
+                "type": "object",
+                "required": ["id", "samples"],
+                "properties": {
+                    "id": { "type": "string" },
+                    "samples": { "type": "integer" },
+                },
+            },
+            "Feedback": {
+                "type": "object",
+                "required": ["strokes", "ranking", "chosen"],
+                "properties": {
+                    "strokes": schema("Sample"),
+                    "ranking": { "type": "array", "items": { "type": "string" } },
+                    "chosen": { "type": "string" },
+                },
+            },
+            "Classifier": {
+                "type": "object",
+                "required": ["name", "symbols", "samples"],
+                "properties": {
+                    "name": { "type": "string" },
+                    "symbols": { "type": "integer" },
+                    "samples": { "type": "integer" },
+                },
+            },
+            "Snapshot": {
+                "type": "object",
+                "description": "Preprocessed samples by base32 symbol id, uploads may use the base64 ids of the original detexify snapshots instead",
+                "additionalProperties": { "type": "array", "items": {} },
+            },
+            "Message": {
+                "type": "object",
+                "required": ["message"],
+                "properties": { "message": { "type": "string" } },
+            },
+            "Error": {
+                "type": "object",
+                "required": ["error"],
+                "properties": {
+                    "error": {
+                        "type": "object",
+                        "required": ["code", "message"],
+                        "properties": {
+                            "code": { "type": "string" },
+                            "message": { "type": "string" },
+                        },
+                    },
+                },
+            },
+        },
+        "securitySchemes": {
+            "snapshotToken": { "type": "http", "scheme": "bearer" },
+        },
+    })
+}
+
+/// Operations by path and method, `document` only documents the routed ones
+fn operations() -> Value {
+    let name = || path_parameter("name", "Classifier name");
+    let authorized = json!([{ "snapshotToken": [] }]);
+    let unauthorized = error("Missing or wrong token");
+    let forbidden = error("No snapshot token is set");
+
+    json!({
+        "/": {
+            "get": {
+                "summary": "Server name and version",
+                "responses": { "200": ok("Server info", json!({ "type": "object" })) },
+            },
+        },
+        "/healthz": {
+            "get": {
+                "summary": "Liveness probe",
+                "responses": { "200": ok("The server is up", json!({ "type": "object" })) },
+            },
+        },
+        "/readyz": {
+            "get": {
+                "summary": "Readiness probe",
+                "responses": {
+                    "200": ok("The classifier has samples", json!({ "type": "object" })),
+                    "503": error("The classifier has no samples"),
+                },
+            },
+        },
+        "/metrics": {
+            "get": {
+                "summary": "Prometheus metrics",
+                "responses": {
+                    "200": {
+                        "description": "Metrics in the Prometheus text format",
+                        "content": { "text/plain": { "schema": { "type": "string" } } },
+                    },
+                },
+            },
+        },
+        "/openapi.json": {
+            "get": {
+                "summary": "This document",
+                "responses": { "200": ok("OpenAPI document", json!({ "type": "object" })) },
+            },
+        },
+        "/classify": {
+            "post": classify_operation("Classify a sample", vec![]),
+        },
+        "/classify/batch": {
+            "post": {
+                "summary": "Classify several samples",
+                "parameters": classify_parameters(),
+                "requestBody": body(json!({ "type": "array", "items": schema("ClassifyRequest") })),
+                "responses": {
+                    "200": ok(
+                        "Results of each sample in order",
+                        json!({ "type": "array", "items": schema("ClassifyResponse") }),
+                    ),
+                    "400": error("Invalid JSON or query parameter"),
+                    "403": error("`reject` is set but no rejection tolerance is"),
+                    "413": error("Too many samples, strokes or points"),
+                    "422": error("Empty sample or invalid coordinates"),
+                },
+            },
+        },
+        "/classify/ws": {
+            "get": {
+                "summary": "Classify while drawing over a WebSocket",
+                "description": format!(
+                    "Messages are described in the server's README, replies hold at most {} results unless `limit` is set",
+                    live::DEFAULT_LIMIT
+                ),
+                "parameters": classify_parameters(),
+                "responses": { "101": { "description": "Switching to the WebSocket protocol" } },
+            },
+        },
+        "/train/{id}": {
+            "post": train_operation("Add a sample of a symbol", vec![]),
+        },
+        "/hs/classify": {
+            "post": {
+                "summary": "Classify a sample in the format of the Haskell backend",
+                "requestBody": {
+                    "required": true,
+                    "content": {
+                        "application/x-www-form-urlencoded": {
+                            "schema": {
+                                "type": "object",
+                                "required": ["strokes"],
+                                "properties": {
+                                    "strokes": {
+                                        "type": "string",
+                                        "description": "A `Sample` as a JSON string",
+                                    },
+                                },
+                            },
+                        },
+                    },
+                },
+                "responses": {
+                    "200": ok("Matching symbols, best first", json!({ "type": "array", "items": schema("HsScore") })),
+                    "400": error("Invalid form or strokes"),
+                    "413": error("Too many strokes or points"),
+                    "422": error("Empty sample or invalid coordinates"),
+                },
+            },
+        },
+        "/hs/train": {
+            "post": {
+                "summary": "Add a sample of a symbol in the format of the Haskell backend",
+                "requestBody": {
+                    "required": true,
+                    "content": {
+                        "application/x-www-form-urlencoded": {
+                            "schema": {
+                                "type": "object",
+                                "required": ["id", "strokes"],
+                                "properties": {
+                                    "id": { "type": "string", "description": "Base64 symbol id" },
+                                    "strokes": {
+                                        "type": "string",
+                                        "description": "A `Sample` as a JSON string",
+                                    },
+                                },
+                            },
+                        },
+                    },
+                },
+                "responses": {
+                    "200": ok("Sample added", schema("Message")),
+                    "400": error("Invalid form or strokes"),
+                    "404": error("Unknown symbol"),
+                    "413": error("Too many strokes or points"),
+                    "422": error("Empty sample or invalid coordinates"),
+                },
+            },
+        },
+        "/feedback": {
+            "post": {
+                "summary": "Record the symbol a user picked",
+                "requestBody": body(schema("Feedback")),
+                "responses": {
+                    "200": ok("Feedback recorded", schema("Message")),
+                    "403": error("Feedback is disabled"),
+                    "404": error("Unknown symbol"),
+                },
+            },
+        },
+        "/symbols": {
+            "get": {
+                "summary": "List the symbol table",
+                "parameters": [
+                    query_parameter("package", "Only symbols from this package", json!({ "type": "string" })),
+                    query_parameter("mode", "Only symbols usable in this mode", schema("Mode")),
+                    query_parameter("q", "Search the commands", json!({ "type": "string" })),
+                ],
+                "responses": {
+                    "200": ok("Symbols", json!({ "type": "array", "items": schema("Symbol") })),
+                    "400": error("Invalid query parameter"),
+                },
+            },
+        },
+        "/snapshot": {
+            "get": {
+                "summary": "Download the classifier's samples",
+                "parameters": [
+                    query_parameter(
+                        "encoding",
+                        "Encoding of the symbol ids, base64 for the original detexify backend",
+                        json!({ "type": "string", "enum": ["base32", "base64"], "default": "base32" }),
+                    ),
+                ],
+                "responses": {
+                    "200": ok("Snapshot", schema("Snapshot")),
+                    "400": error("Invalid query parameter"),
+                },
+            },
+            "put": {
+                "summary": "Replace the classifier's samples",
+                "security": authorized,
+                "requestBody": body(schema("Snapshot")),
+                "responses": {
+                    "200": ok("Snapshot replaced", schema("Message")),
+                    "400": error("Invalid snapshot"),
+                    "401": unauthorized,
+                    "403": forbidden,
+                },
+            },
+        },
+        "/c": {
+            "get": {
+                "summary": "List the named classifiers",
+                "responses": {
+                    "200": ok("Named classifiers", json!({ "type": "array", "items": schema("Classifier") })),
+                },
+            },
+        },
+        "/c/{name}": {
+            "get": {
+                "summary": "Sample counts of a named classifier",
+                "parameters": [name()],
+                "responses": {
+                    "200": ok("Named classifier", schema("Classifier")),
+                    "404": error("Unknown classifier"),
+                },
+            },
+            "put": {
+                "summary": "Create a named classifier",
+                "security": authorized,
+                "parameters": [name()],
+                "responses": {
+                    "201": ok("Named classifier", schema("Classifier")),
+                    "400": error("Invalid name"),
+                    "401": unauthorized,
+                    "403": forbidden,
+                    "409": error("The classifier already exists"),
+                },
+            },
+            "delete": {
+                "summary": "Delete a named classifier and its samples",
+                "security": authorized,
+                "parameters": [name()],
+                "responses": {
+                    "200": ok("Classifier deleted", schema("Message")),
+                    "401": unauthorized,
+                    "403": forbidden,
+                    "404": error("Unknown classifier"),
+                },
+            },
+        },
+        "/c/{name}/classify": {
+            "post": classify_operation("Classify a sample with a named classifier", vec![name()]),
+        },
+        "/c/{name}/train/{id}": {
+            "post": train_operation("Add a sample of a symbol to a named classifier", vec![name()]),
+        },
+    })
+}
+
+/// The OpenAPI document of every route
+pub fn document() -> Value {
+    let mut operations = operations();
+    let mut paths = Map::new();
+    for route in all_routes() {
+        let method = route.method.as_str().to_lowercase();
+        let mut operation = operations[route.path][&method].take();
+        if route.limited {
+            operation["responses"]["429"] = error("Too many requests from this client");
+        }
+        paths.entry(route.path).or_insert_with(|| json!({}))[&method] = operation;
+    }
+
+    json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "detexify-server",
+            "description": "Rust port of the detexify backend",
+            "version": env!("CARGO_PKG_VERSION"),
+        },
+        "paths": paths,
+        "components": components(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeSet;
+
+    #[test]
+    fn test_document_matches_routes() {
+        let routed: BTreeSet<(String, String)> = all_routes()
+            .iter()
+            .map(|route| (route.path.to_string(), route.method.as_str().to_lowercase()))
+            .collect();
+        let documented: BTreeSet<(String, String)> = operations()
+            .as_object()
+            .unwrap()
+            .iter()
+            .flat_map(|(path, operations)| {
+                let methods = operations.as_object().unwrap().keys();
+                methods.map(move |method| (path.clone(), method.clone()))
+            })
+            .collect();
+
+        assert_eq!(routed, documented);
+    }
+
+    #[test]
+    fn test_references_resolve() {
+        let document = document();
+        let text = document.to_string();
+        for reference in text.split("\"#/components/schemas/").skip(1) {
+            let name = reference.split('"').next().unwrap();
+            assert!(
+                document["components"]["schemas"].get(name).is_some(),
+                "missing schema {}",
+                name
+            );
+        }
+    }
+}
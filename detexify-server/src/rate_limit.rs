@@ -0,0 +1,93 @@
+use crate::error::{ApiError, ErrorCode};
+use crate::AppState;
+use axum::extract::{ConnectInfo, Request, State};
+use axum::http::{header, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use governor::clock::{Clock, DefaultClock};
+use governor::{DefaultKeyedRateLimiter, Quota};
+use std::net::{IpAddr, SocketAddr};
+use std::num::NonZeroU32;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Limits the request rate of each client IP address
+pub struct RateLimiter {
+    limiter: DefaultKeyedRateLimiter<IpAddr>,
+    clock: DefaultClock,
+}
+
+impl RateLimiter {
+    /// Allows `per_second` requests per second on average with bursts of up to `burst` requests
+    pub fn new(per_second: NonZeroU32, burst: NonZeroU32) -> RateLimiter {
+        RateLimiter {
+            limiter: DefaultKeyedRateLimiter::keyed(
+                Quota::per_second(per_second).allow_burst(burst),
+            ),
+            clock: DefaultClock::default(),
+        }
+    }
+
+    /// Takes a request from the budget of `client`, or returns how long until it has one
+    pub fn check(&self, client: IpAddr) -> Result<(), Duration> {
+        self.limiter
+            .check_key(&client)
+            .map_err(|not_until| not_until.wait_time_from(self.clock.now()))
+    }
+
+    /// Forgets clients whose budget has refilled, call periodically to bound memory use
+    pub fn retain_recent(&self) {
+        self.limiter.retain_recent();
+        self.limiter.shrink_to_fit();
+    }
+}
+
+/// Error answering a request from a client over the rate limit
+pub(crate) fn rate_limited() -> ApiError {
+    ApiError::new(
+        StatusCode::TOO_MANY_REQUESTS,
+        ErrorCode::RateLimited,
+        "too many requests, slow down",
+    )
+}
+
+/// Middleware rejecting requests from clients over the rate limit. Clients are identified by the
+/// peer address, requests are let through when the server isn't serving with connect info.
+pub async fn limit(State(state): State<Arc<AppState>>, request: Request, next: Next) -> Response {
+    let limiter = match &state.rate_limiter {
+        Some(limiter) => limiter,
+        None => return next.run(request).await,
+    };
+    let client = match request.extensions().get::<ConnectInfo<SocketAddr>>() {
+        Some(ConnectInfo(address)) => address.ip(),
+        None => return next.run(request).await,
+    };
+
+    match limiter.check(client) {
+        Ok(()) => next.run(request).await,
+        Err(wait) => {
+            let error = rate_limited();
+            // round up so clients retrying after the header don't hit the limit again
+            let retry_after = wait.as_secs() + u64::from(wait.subsec_nanos() > 0);
+            ([(header::RETRY_AFTER, retry_after.to_string())], error).into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    #[test]
+    fn test_rate_limiter() {
+        let limiter = RateLimiter::new(NonZeroU32::new(1).unwrap(), NonZeroU32::new(2).unwrap());
+        let a = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+        let b = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2));
+
+        assert!(limiter.check(a).is_ok());
+        assert!(limiter.check(a).is_ok());
+        assert!(limiter.check(a).is_err());
+        assert!(limiter.check(b).is_ok());
+    }
+}
@@ -0,0 +1,606 @@
+use crate::classify::{self, ClassifyOptions, ClassifyRequest, ClassifyResponse};
+use crate::compat::{self, HsClassifyForm, HsScore, HsTrainForm};
+use crate::error::{ApiError, ErrorCode};
+use crate::feedback::{FeedbackRecord, FeedbackRequest};
+use crate::live;
+use crate::openapi;
+use crate::store::Store;
+use crate::symbols::{self, SymbolInfo};
+use crate::tenants::{TenantError, MAX_NAME_LENGTH};
+use crate::validation::{sample_from_strokes, MAX_BATCH_SIZE};
+use crate::workers::Workers;
+use crate::AppState;
+use axum::body::Bytes;
+use axum::extract::rejection::{FormRejection, JsonRejection, QueryRejection};
+use axum::extract::ws::WebSocketUpgrade;
+use axum::extract::{ConnectInfo, FromRequestParts, Path, Query, State};
+use axum::http::{header, request::Parts, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::{Extension, Form, Json};
+use detexify::{
+    base64_to_base32, Classifier, IdEncoding, Mode, Stroke, StrokeSample, Symbol, SymbolFilter,
+};
+use rayon::prelude::*;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Instant;
+
+type AppStateRef = State<Arc<AppState>>;
+
+/// Extractor for requests with an `Authorization: Bearer <token>` header matching the snapshot
+/// token
+pub struct Authorized;
+
+impl FromRequestParts<Arc<AppState>> for Authorized {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &Arc<AppState>,
+    ) -> Result<Self, Self::Rejection> {
+        let expected = match &state.snapshot_token {
+            Some(token) => format!("Bearer {}", token),
+            None => {
+                return Err(ApiError::new(
+                    StatusCode::FORBIDDEN,
+                    ErrorCode::Forbidden,
+                    "this route is disabled as no snapshot token is set",
+                ))
+            }
+        };
+
+        match parts.headers.get(header::AUTHORIZATION) {
+            Some(header) if constant_time_eq(header.as_bytes(), expected.as_bytes()) => {
+                Ok(Authorized)
+            }
+            _ => Err(ApiError::new(
+                StatusCode::UNAUTHORIZED,
+                ErrorCode::Unauthorized,
+                "missing or invalid authorization token",
+            )),
+        }
+    }
+}
+
+/// Compares in a time that doesn't depend on where `a` and `b` differ, so a token can't be
+/// guessed a byte at a time
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+/// Runs CPU heavy or blocking work off the async runtime once one of `workers` is free
+pub(crate) async fn blocking<T, F>(workers: Workers, f: F) -> Result<T, ApiError>
+where
+    F: FnOnce() -> Result<T, ApiError> + Send + 'static,
+    T: Send + 'static,
+{
+    workers.run(f).await
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct ClassifyQuery {
+    limit: Option<usize>,
+    mode: Option<Mode>,
+    /// Comma separated
+    packages: Option<String>,
+    reject: Option<bool>,
+}
+
+impl From<ClassifyQuery> for ClassifyOptions {
+    fn from(query: ClassifyQuery) -> Self {
+        ClassifyOptions {
+            limit: query.limit,
+            mode: query.mode,
+            packages: query
+                .packages
+                .map(|p| p.split(',').map(str::to_string).collect()),
+            reject: query.reject,
+        }
+    }
+}
+
+pub async fn get_root() -> Json<Value> {
+    Json(json!({
+        "server": "rust detexify server",
+        "version": env!("CARGO_PKG_VERSION"),
+    }))
+}
+
+pub async fn get_healthz() -> Json<Value> {
+    Json(json!({ "status": "ok" }))
+}
+
+/// Ready once the classifier has samples to classify against
+pub async fn get_readyz(State(state): AppStateRef) -> Result<Json<Value>, ApiError> {
+    let classifier = state.store.read();
+    if classifier.sample_count() == 0 {
+        return Err(ApiError::new(
+            StatusCode::SERVICE_UNAVAILABLE,
+            ErrorCode::NotReady,
+            "the classifier has no samples",
+        ));
+    }
+
+    Ok(Json(json!({
+        "status": "ready",
+        "symbols": classifier.symbol_count(),
+        "samples": classifier.sample_count(),
+    })))
+}
+
+pub async fn get_metrics(State(state): AppStateRef) -> impl IntoResponse {
+    let metrics = state.metrics.render(&state.store.read());
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        metrics,
+    )
+}
+
+pub async fn get_openapi() -> Json<Value> {
+    Json(openapi::document())
+}
+
+pub async fn post_classify(
+    State(state): AppStateRef,
+    query: Result<Query<ClassifyQuery>, QueryRejection>,
+    request: Result<Json<ClassifyRequest>, JsonRejection>,
+) -> Result<Json<ClassifyResponse>, ApiError> {
+    let (strokes, options) = request?.0.into_parts(query?.0.into());
+    check_reject(&state, &options)?;
+    let sample = sample_from_strokes(strokes, &state.limits)?;
+
+    blocking(state.workers.clone(), move || {
+        Ok(Json(classify_sample(
+            &state,
+            None,
+            sample,
+            options,
+            "/classify",
+        )))
+    })
+    .await
+}
+
+/// `POST /classify` of the Haskell backend, strokes are a JSON string in a form field
+pub async fn post_hs_classify(
+    State(state): AppStateRef,
+    form: Result<Form<HsClassifyForm>, FormRejection>,
+) -> Result<Json<Vec<HsScore>>, ApiError> {
+    let strokes = hs_strokes(&form?.0.strokes)?;
+    let sample = sample_from_strokes(strokes, &state.limits)?;
+
+    blocking(state.workers.clone(), move || {
+        let results = classify_sample(
+            &state,
+            None,
+            sample,
+            ClassifyOptions::default(),
+            "/hs/classify",
+        );
+        Ok(Json(compat::scores(results.into_results())))
+    })
+    .await
+}
+
+/// `POST /train` of the Haskell backend, the base64 symbol id and the strokes as a JSON string
+/// are form fields
+pub async fn post_hs_train(
+    State(state): AppStateRef,
+    form: Result<Form<HsTrainForm>, FormRejection>,
+) -> Result<Json<Value>, ApiError> {
+    let form = form?.0;
+    let id = base64_to_base32(&form.id).unwrap_or(form.id);
+    check_symbol(&state, &id)?;
+    let sample = sample_from_strokes(hs_strokes(&form.strokes)?, &state.limits)?;
+
+    blocking(state.workers.clone(), move || {
+        state
+            .store
+            .train(id, sample)
+            .map_err(|e| ApiError::internal("failed to record sample", e))?;
+
+        Ok(Json(
+            json!({ "message": "Symbol was successfully trained." }),
+        ))
+    })
+    .await
+}
+
+/// Parses the strokes of the Haskell backend's forms
+fn hs_strokes(strokes: &str) -> Result<Vec<Stroke>, ApiError> {
+    serde_json::from_str(strokes).map_err(|e| {
+        ApiError::new(
+            StatusCode::BAD_REQUEST,
+            ErrorCode::BadJson,
+            format!("invalid strokes: {}", e),
+        )
+    })
+}
+
+/// Answers requests for rejection with `403` when no rejection tolerance is set, as nothing
+/// would ever be rejected
+pub(crate) fn check_reject(state: &AppState, options: &ClassifyOptions) -> Result<(), ApiError> {
+    if options.reject == Some(true) && state.store.read().rejection_tolerance().is_none() {
+        return Err(ApiError::new(
+            StatusCode::FORBIDDEN,
+            ErrorCode::Forbidden,
+            "rejection is disabled as no rejection tolerance is set",
+        ));
+    }
+
+    Ok(())
+}
+
+/// Classifies a single sample with the server's classifier, layered under `tenant` if given,
+/// recording the time taken under `route`
+pub(crate) fn classify_sample(
+    state: &AppState,
+    tenant: Option<&Store>,
+    sample: StrokeSample,
+    options: ClassifyOptions,
+    route: &str,
+) -> ClassifyResponse {
+    let start = Instant::now();
+    let base = state.store.read();
+    let scores = match tenant {
+        Some(tenant) => tenant.read().classify_with_base(&base, sample),
+        None => base.classify(sample),
+    };
+    let mut scores = scores.unwrap_or_default();
+    state.metrics.observe_classify(route, 1, start);
+
+    scores.retain(|s| state.allows(&s.id));
+    // named classifiers are layered on the server's classifier so share its thresholds
+    let unknown = scores.first().is_some_and(|best| base.rejects(best));
+    classify::response(scores, unknown, options)
+}
+
+pub async fn get_classify_ws(
+    State(state): AppStateRef,
+    connect_info: Option<Extension<ConnectInfo<SocketAddr>>>,
+    query: Result<Query<ClassifyQuery>, QueryRejection>,
+    ws: WebSocketUpgrade,
+) -> Result<Response, ApiError> {
+    let mut options: ClassifyOptions = query?.0.into();
+    options.limit = options.limit.or(Some(live::DEFAULT_LIMIT));
+    check_reject(&state, &options)?;
+    // the same client as the rate limit middleware, absent when not serving with connect info
+    let client = connect_info.map(|Extension(ConnectInfo(address))| address.ip());
+    Ok(ws
+        .max_message_size(state.max_request_size)
+        .on_upgrade(move |socket| live::run(socket, state, options, client)))
+}
+
+pub async fn post_classify_batch(
+    State(state): AppStateRef,
+    query: Result<Query<ClassifyQuery>, QueryRejection>,
+    requests: Result<Json<Vec<ClassifyRequest>>, JsonRejection>,
+) -> Result<Json<Vec<ClassifyResponse>>, ApiError> {
+    let requests = requests?.0;
+    if requests.len() > MAX_BATCH_SIZE {
+        return Err(ApiError::new(
+            StatusCode::PAYLOAD_TOO_LARGE,
+            ErrorCode::TooManySamples,
+            format!(
+                "batch has {} samples, the limit is {}",
+                requests.len(),
+                MAX_BATCH_SIZE
+            ),
+        ));
+    }
+
+    let defaults: ClassifyOptions = query?.0.into();
+    let samples = requests
+        .into_iter()
+        .enumerate()
+        .map(|(i, request)| {
+            let (strokes, options) = request.into_parts(defaults.clone());
+            check_reject(&state, &options)?;
+            let sample = sample_from_strokes(strokes, &state.limits).map_err(|mut e| {
+                e.message = format!("sample {}: {}", i, e.message);
+                e
+            })?;
+
+            Ok((sample, options))
+        })
+        .collect::<Result<Vec<_>, ApiError>>()?;
+
+    blocking(state.workers.clone(), move || {
+        let start = Instant::now();
+        let count = samples.len();
+        let guard = state.store.read();
+        let classifier: &Classifier = &guard;
+        let results = samples
+            .into_par_iter()
+            .map(|(sample, options)| {
+                let mut scores = classifier.classify(sample).unwrap_or_default();
+                scores.retain(|s| state.allows(&s.id));
+                let unknown = scores.first().is_some_and(|best| classifier.rejects(best));
+                classify::response(scores, unknown, options)
+            })
+            .collect();
+        state
+            .metrics
+            .observe_classify("/classify/batch", count, start);
+
+        Ok(Json(results))
+    })
+    .await
+}
+
+pub async fn post_train(
+    State(state): AppStateRef,
+    Path(id): Path<String>,
+    strokes: Result<Json<Vec<Stroke>>, JsonRejection>,
+) -> Result<Json<Value>, ApiError> {
+    let store = state.store.clone();
+    train_sample(&state, store, id, strokes).await
+}
+
+async fn train_sample(
+    state: &AppState,
+    store: Arc<Store>,
+    id: String,
+    strokes: Result<Json<Vec<Stroke>>, JsonRejection>,
+) -> Result<Json<Value>, ApiError> {
+    check_symbol(state, &id)?;
+    let sample = sample_from_strokes(strokes?.0, &state.limits)?;
+
+    blocking(state.workers.clone(), move || {
+        let samples = store
+            .train(id.clone(), sample)
+            .map_err(|e| ApiError::internal("failed to record sample", e))?;
+
+        Ok(Json(json!({
+            "id": id,
+            "samples": samples,
+        })))
+    })
+    .await
+}
+
+/// Checks `id` is a symbol the server knows
+fn check_symbol(state: &AppState, id: &str) -> Result<(), ApiError> {
+    if Symbol::from_id(id).is_none() || !state.allows(id) {
+        return Err(ApiError::new(
+            StatusCode::NOT_FOUND,
+            ErrorCode::UnknownSymbol,
+            format!("unknown symbol id {}", id),
+        ));
+    }
+
+    Ok(())
+}
+
+pub async fn post_feedback(
+    State(state): AppStateRef,
+    request: Result<Json<FeedbackRequest>, JsonRejection>,
+) -> Result<Json<Value>, ApiError> {
+    if state.feedback.is_none() {
+        return Err(ApiError::new(
+            StatusCode::FORBIDDEN,
+            ErrorCode::Forbidden,
+            "feedback is disabled",
+        ));
+    }
+
+    let request = request?.0;
+    check_symbol(&state, &request.chosen)?;
+    // only keep drawings /classify would accept
+    sample_from_strokes(request.strokes.clone(), &state.limits)?;
+
+    blocking(state.workers.clone(), move || {
+        let record = FeedbackRecord::new(request.strokes, request.ranking, request.chosen);
+        if let Some(feedback) = &state.feedback {
+            feedback
+                .append(&record)
+                .map_err(|e| ApiError::internal("failed to record feedback", e))?;
+        }
+
+        Ok(Json(json!({ "message": "feedback recorded" })))
+    })
+    .await
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SymbolsQuery {
+    package: Option<String>,
+    mode: Option<Mode>,
+    q: Option<String>,
+}
+
+pub async fn get_symbols(
+    State(state): AppStateRef,
+    query: Result<Query<SymbolsQuery>, QueryRejection>,
+) -> Result<Json<Vec<SymbolInfo>>, ApiError> {
+    let query = query?.0;
+    let filter = SymbolFilter {
+        packages: query.package.map(|p| vec![p]),
+        mode: query.mode,
+        query: query.q,
+    };
+
+    let mut symbols: Vec<SymbolInfo> = symbols::table()
+        .iter()
+        .filter(|(id, s)| filter.matches(s) && state.allows(id))
+        .map(|(id, s)| SymbolInfo::new(id, s.clone()))
+        .collect();
+    symbols.sort_by(|a, b| (a.package, a.command).cmp(&(b.package, b.command)));
+
+    Ok(Json(symbols))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SnapshotQuery {
+    encoding: Option<IdEncoding>,
+}
+
+pub async fn get_snapshot(
+    State(state): AppStateRef,
+    query: Result<Query<SnapshotQuery>, QueryRejection>,
+) -> Result<impl IntoResponse, ApiError> {
+    let encoding = query?.0.encoding.unwrap_or(IdEncoding::Base32);
+    let snapshot = blocking(state.workers.clone(), move || {
+        let mut snapshot = Vec::new();
+        state
+            .store
+            .read()
+            .to_snapshot_with_encoding(&mut snapshot, encoding)
+            .map_err(|e| ApiError::internal("failed to write snapshot", e))?;
+        Ok(snapshot)
+    })
+    .await?;
+
+    Ok(([(header::CONTENT_TYPE, "application/json")], snapshot))
+}
+
+pub async fn put_snapshot(
+    _authorized: Authorized,
+    State(state): AppStateRef,
+    snapshot: Bytes,
+) -> Result<Json<Value>, ApiError> {
+    blocking(state.workers.clone(), move || {
+        // parse before taking the lock so classification isn't blocked by a slow upload
+        let classifier = Classifier::from_snapshot(&snapshot[..]).map_err(|e| {
+            ApiError::new(
+                StatusCode::BAD_REQUEST,
+                ErrorCode::InvalidSnapshot,
+                format!("invalid snapshot: {}", e),
+            )
+        })?;
+
+        state
+            .store
+            .replace(classifier)
+            .map_err(|e| ApiError::internal("failed to save snapshot", e))?;
+
+        Ok(Json(json!({ "message": "snapshot replaced" })))
+    })
+    .await
+}
+
+fn tenant(state: &AppState, name: &str) -> Result<Arc<Store>, ApiError> {
+    state.tenants.get(name).ok_or_else(|| {
+        ApiError::new(
+            StatusCode::NOT_FOUND,
+            ErrorCode::UnknownClassifier,
+            format!("unknown classifier {}", name),
+        )
+    })
+}
+
+fn tenant_info(name: &str, store: &Store) -> Value {
+    let classifier = store.read();
+    json!({
+        "name": name,
+        "symbols": classifier.symbol_count(),
+        "samples": classifier.sample_count(),
+    })
+}
+
+pub async fn get_classifiers(State(state): AppStateRef) -> Json<Vec<Value>> {
+    let tenants = state
+        .tenants
+        .names()
+        .into_iter()
+        .filter_map(|name| {
+            let store = state.tenants.get(&name)?;
+            Some(tenant_info(&name, &store))
+        })
+        .collect();
+
+    Json(tenants)
+}
+
+pub async fn get_classifier(
+    State(state): AppStateRef,
+    Path(name): Path<String>,
+) -> Result<Json<Value>, ApiError> {
+    let store = tenant(&state, &name)?;
+    Ok(Json(tenant_info(&name, &store)))
+}
+
+pub async fn put_classifier(
+    _authorized: Authorized,
+    State(state): AppStateRef,
+    Path(name): Path<String>,
+) -> Result<(StatusCode, Json<Value>), ApiError> {
+    blocking(state.workers.clone(), move || {
+        let store = state.tenants.create(&name).map_err(|e| match e {
+            TenantError::InvalidName => ApiError::new(
+                StatusCode::BAD_REQUEST,
+                ErrorCode::InvalidParameter,
+                format!(
+                    "classifier names are 1 to {} lowercase letters, digits, - or _",
+                    MAX_NAME_LENGTH
+                ),
+            ),
+            TenantError::Exists => ApiError::new(
+                StatusCode::CONFLICT,
+                ErrorCode::ClassifierExists,
+                format!("classifier {} already exists", name),
+            ),
+            TenantError::Io(e) => ApiError::internal("failed to create classifier", e),
+        })?;
+
+        Ok((StatusCode::CREATED, Json(tenant_info(&name, &store))))
+    })
+    .await
+}
+
+pub async fn delete_classifier(
+    _authorized: Authorized,
+    State(state): AppStateRef,
+    Path(name): Path<String>,
+) -> Result<Json<Value>, ApiError> {
+    blocking(state.workers.clone(), move || {
+        let deleted = state
+            .tenants
+            .delete(&name)
+            .map_err(|e| ApiError::internal("failed to delete classifier", e))?;
+        if !deleted {
+            return Err(ApiError::new(
+                StatusCode::NOT_FOUND,
+                ErrorCode::UnknownClassifier,
+                format!("unknown classifier {}", name),
+            ));
+        }
+
+        Ok(Json(json!({ "message": "classifier deleted" })))
+    })
+    .await
+}
+
+pub async fn post_tenant_classify(
+    State(state): AppStateRef,
+    Path(name): Path<String>,
+    query: Result<Query<ClassifyQuery>, QueryRejection>,
+    request: Result<Json<ClassifyRequest>, JsonRejection>,
+) -> Result<Json<ClassifyResponse>, ApiError> {
+    let store = tenant(&state, &name)?;
+    let (strokes, options) = request?.0.into_parts(query?.0.into());
+    check_reject(&state, &options)?;
+    let sample = sample_from_strokes(strokes, &state.limits)?;
+
+    blocking(state.workers.clone(), move || {
+        Ok(Json(classify_sample(
+            &state,
+            Some(&store),
+            sample,
+            options,
+            "/c/{name}/classify",
+        )))
+    })
+    .await
+}
+
+pub async fn post_tenant_train(
+    State(state): AppStateRef,
+    Path((name, id)): Path<(String, String)>,
+    strokes: Result<Json<Vec<Stroke>>, JsonRejection>,
+) -> Result<Json<Value>, ApiError> {
+    let store = tenant(&state, &name)?;
+    train_sample(&state, store, id, strokes).await
+}
@@ -0,0 +1,327 @@
+use detexify::{Classifier, StrokeSample};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, RwLock, RwLockReadGuard};
+
+const SNAPSHOT_FILE: &str = "snapshot.json";
+const SNAPSHOT_TMP_FILE: &str = "snapshot.json.tmp";
+const JOURNAL_FILE: &str = "journal.jsonl";
+/// The journal a snapshot being written replaces, deleting it commits the snapshot
+const ROTATED_JOURNAL_FILE: &str = "journal.old.jsonl";
+
+#[derive(Serialize, Deserialize)]
+struct JournalEntry {
+    id: String,
+    sample: StrokeSample,
+}
+
+struct Disk {
+    dir: PathBuf,
+    /// Training samples since the last snapshot, one JSON entry per line. Always locked before
+    /// the classifier so the journal and snapshot can't miss a sample.
+    journal: Mutex<File>,
+}
+
+/// The server's classifier, optionally persisted to a data directory as a snapshot plus a
+/// journal of samples trained since the snapshot was written
+pub struct Store {
+    classifier: RwLock<Classifier>,
+    disk: Option<Disk>,
+}
+
+impl Store {
+    /// Creates a store without persistence
+    pub fn in_memory(classifier: Classifier) -> Store {
+        Store {
+            classifier: RwLock::new(classifier),
+            disk: None,
+        }
+    }
+
+    /// Opens the data directory `dir`, loading its snapshot (or the `initial` classifier if there
+    /// is none yet) and replaying its journal
+    pub fn open<F>(dir: PathBuf, initial: F) -> io::Result<Store>
+    where
+        F: FnOnce() -> io::Result<Classifier>,
+    {
+        fs::create_dir_all(&dir)?;
+
+        let snapshot_path = dir.join(SNAPSHOT_FILE);
+        let load = || {
+            if snapshot_path.exists() {
+                Ok(Classifier::from_snapshot(BufReader::new(File::open(
+                    &snapshot_path,
+                )?))?)
+            } else {
+                initial()
+            }
+        };
+
+        let rotated_path = dir.join(ROTATED_JOURNAL_FILE);
+        let tmp_path = dir.join(SNAPSHOT_TMP_FILE);
+        let mut classifier = if rotated_path.exists() {
+            // stopped while writing a snapshot, before it was committed: redo it without the
+            // samples journaled since
+            let mut classifier = load()?;
+            replay(&rotated_path, &mut classifier)?;
+            commit_snapshot(&dir, &classifier)?;
+            classifier
+        } else {
+            if tmp_path.exists() {
+                // stopped after committing a snapshot, before it was moved into place
+                fs::rename(&tmp_path, &snapshot_path)?;
+            }
+            load()?
+        };
+
+        let journal_path = dir.join(JOURNAL_FILE);
+        if journal_path.exists() {
+            replay(&journal_path, &mut classifier)?;
+        }
+
+        let journal = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&journal_path)?;
+
+        Ok(Store {
+            classifier: RwLock::new(classifier),
+            disk: Some(Disk {
+                dir,
+                journal: Mutex::new(journal),
+            }),
+        })
+    }
+
+    pub fn read(&self) -> RwLockReadGuard<'_, Classifier> {
+        self.classifier.read().unwrap()
+    }
+
+    /// Trains the classifier, appending the sample to the journal first. Returns the number of
+    /// samples the symbol now has.
+    pub fn train(&self, id: String, sample: StrokeSample) -> io::Result<usize> {
+        let _journal = match &self.disk {
+            Some(disk) => {
+                let mut journal = disk.journal.lock().unwrap();
+                let entry = JournalEntry {
+                    id: id.clone(),
+                    sample: sample.clone(),
+                };
+                writeln!(journal, "{}", serde_json::to_string(&entry)?)?;
+                Some(journal)
+            }
+            None => None,
+        };
+
+        Ok(self.classifier.write().unwrap().train(id, sample))
+    }
+
+    /// Sets the softmax temperature of the classifier, which is kept when it is replaced
+    pub fn set_temperature(&self, temperature: f64) {
+        self.classifier
+            .write()
+            .unwrap()
+            .set_temperature(temperature);
+    }
+
+    /// Learns the rejection thresholds of the classifier, which are kept up to date by training
+    /// and relearned when it is replaced
+    pub fn learn_thresholds(&self, tolerance: f64) {
+        self.classifier.write().unwrap().learn_thresholds(tolerance);
+    }
+
+    /// Replaces the classifier, persisting it as the new snapshot. The current temperature and
+    /// rejection tolerance are kept as snapshots only hold samples.
+    pub fn replace(&self, mut classifier: Classifier) -> io::Result<()> {
+        let (temperature, tolerance) = {
+            let current = self.read();
+            (current.temperature(), current.rejection_tolerance())
+        };
+        classifier.set_temperature(temperature);
+        if let Some(tolerance) = tolerance {
+            classifier.learn_thresholds(tolerance);
+        }
+        match &self.disk {
+            Some(disk) => {
+                let mut journal = disk.journal.lock().unwrap();
+                disk.write_snapshot(&classifier, &mut journal)?;
+                *self.classifier.write().unwrap() = classifier;
+            }
+            None => *self.classifier.write().unwrap() = classifier,
+        }
+
+        Ok(())
+    }
+
+    /// Writes a snapshot and clears the journal, does nothing if nothing was trained since the
+    /// last snapshot or the store isn't persisted
+    pub fn snapshot(&self) -> io::Result<()> {
+        if let Some(disk) = &self.disk {
+            let mut journal = disk.journal.lock().unwrap();
+            if journal.metadata()?.len() == 0 {
+                return Ok(());
+            }
+
+            disk.write_snapshot(&self.classifier.read().unwrap(), &mut journal)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Disk {
+    /// Rotates the journal and commits `classifier` as the snapshot replacing it, rotating the
+    /// journal back if the snapshot couldn't be committed
+    fn write_snapshot(&self, classifier: &Classifier, journal: &mut File) -> io::Result<()> {
+        let journal_path = self.dir.join(JOURNAL_FILE);
+        let rotated_path = self.dir.join(ROTATED_JOURNAL_FILE);
+        let open = || {
+            OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&journal_path)
+        };
+
+        fs::rename(&journal_path, &rotated_path)?;
+        let committed = open().and_then(|fresh| {
+            *journal = fresh;
+            commit_snapshot(&self.dir, classifier)
+        });
+        if committed.is_err() && rotated_path.exists() {
+            // nothing was journaled since as the journal is locked
+            fs::rename(&rotated_path, &journal_path)?;
+            *journal = open()?;
+        }
+        committed
+    }
+}
+
+/// Trains `classifier` with the entries of the journal at `path`
+fn replay(path: &Path, classifier: &mut Classifier) -> io::Result<()> {
+    for (n, line) in BufReader::new(File::open(path)?).lines().enumerate() {
+        match serde_json::from_str::<JournalEntry>(&line?) {
+            Ok(entry) => {
+                classifier.train(entry.id, entry.sample);
+            }
+            // most likely a partial write when the server was stopped
+            Err(e) => eprintln!("skipping journal line {}: {}", n + 1, e),
+        }
+    }
+
+    Ok(())
+}
+
+/// Writes `classifier` as the snapshot replacing the snapshot and rotated journal in `dir`. The
+/// snapshot is complete before the rotated journal is deleted, which commits it, so whichever
+/// step the server is stopped at [`Store::open`] loads each sample once.
+fn commit_snapshot(dir: &Path, classifier: &Classifier) -> io::Result<()> {
+    let tmp_path = dir.join(SNAPSHOT_TMP_FILE);
+    let mut tmp = io::BufWriter::new(File::create(&tmp_path)?);
+    classifier.to_snapshot(&mut tmp)?;
+    tmp.into_inner()?.sync_all()?;
+
+    let rotated_path = dir.join(ROTATED_JOURNAL_FILE);
+    if rotated_path.exists() {
+        fs::remove_file(&rotated_path)?;
+    }
+    fs::rename(&tmp_path, dir.join(SNAPSHOT_FILE))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use detexify::{Point, Stroke};
+
+    #[test]
+    fn test_journal_and_snapshot() {
+        let dir = std::env::temp_dir().join(format!("detexify-store-{}", std::process::id()));
+        let sample = StrokeSample::new(vec![Stroke::new(vec![
+            Point { x: 0.0, y: 0.0 },
+            Point { x: 1.0, y: 1.0 },
+        ])])
+        .unwrap();
+        let open = || Store::open(dir.clone(), || Ok(Classifier::new())).unwrap();
+        let journal_len = || fs::metadata(dir.join(JOURNAL_FILE)).unwrap().len();
+
+        // the journal is replayed when reopened
+        let store = open();
+        store.train("diagonal".to_string(), sample.clone()).unwrap();
+        store.train("diagonal".to_string(), sample.clone()).unwrap();
+        drop(store);
+        let store = open();
+        assert_eq!(store.read().sample_count(), 2);
+        assert!(!dir.join(SNAPSHOT_FILE).exists());
+
+        // snapshots truncate the journal
+        store.snapshot().unwrap();
+        assert!(dir.join(SNAPSHOT_FILE).exists());
+        assert_eq!(journal_len(), 0);
+
+        // a partial last line, as left by a crash mid write, is skipped
+        store.train("diagonal".to_string(), sample).unwrap();
+        drop(store);
+        let mut journal = OpenOptions::new()
+            .append(true)
+            .open(dir.join(JOURNAL_FILE))
+            .unwrap();
+        write!(journal, "{{\"id\": \"diag").unwrap();
+        drop(journal);
+        let store = open();
+        assert_eq!(store.read().sample_count(), 3);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_interrupted_snapshot() {
+        let dir =
+            std::env::temp_dir().join(format!("detexify-store-interrupted-{}", std::process::id()));
+        let sample = StrokeSample::new(vec![Stroke::new(vec![
+            Point { x: 0.0, y: 0.0 },
+            Point { x: 1.0, y: 1.0 },
+        ])])
+        .unwrap();
+        let open = || Store::open(dir.clone(), || Ok(Classifier::new())).unwrap();
+        let entry = serde_json::to_string(&JournalEntry {
+            id: "diagonal".to_string(),
+            sample: sample.clone(),
+        })
+        .unwrap();
+
+        let store = open();
+        store.train("diagonal".to_string(), sample.clone()).unwrap();
+        store.snapshot().unwrap();
+        store.train("diagonal".to_string(), sample).unwrap();
+        drop(store);
+
+        // stopped halfway through writing a snapshot, after rotating the journal and journaling
+        // another sample
+        fs::rename(dir.join(JOURNAL_FILE), dir.join(ROTATED_JOURNAL_FILE)).unwrap();
+        fs::write(dir.join(JOURNAL_FILE), format!("{}\n", entry)).unwrap();
+        fs::write(dir.join(SNAPSHOT_TMP_FILE), "{\"diag").unwrap();
+        let store = open();
+        assert_eq!(store.read().sample_count(), 3);
+        assert!(!dir.join(ROTATED_JOURNAL_FILE).exists());
+        assert!(!dir.join(SNAPSHOT_TMP_FILE).exists());
+
+        // stopped after committing a snapshot by deleting the rotated journal, before moving it
+        // into place
+        let mut snapshot = Vec::new();
+        store.read().to_snapshot(&mut snapshot).unwrap();
+        drop(store);
+        fs::write(dir.join(SNAPSHOT_TMP_FILE), snapshot).unwrap();
+        fs::write(dir.join(JOURNAL_FILE), "").unwrap();
+        let store = open();
+        assert_eq!(store.read().sample_count(), 3);
+        assert!(!dir.join(SNAPSHOT_TMP_FILE).exists());
+        drop(store);
+
+        // either way every sample is loaded once
+        let store = open();
+        assert_eq!(store.read().sample_count(), 3);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
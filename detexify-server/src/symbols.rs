@@ -0,0 +1,47 @@
+use detexify::{iter_symbols, Symbol};
+use serde::Serialize;
+use std::sync::OnceLock;
+
+/// Every symbol with its id, computed once as [`Symbol::id`] leaks a string on every call
+pub fn table() -> &'static [(&'static str, Symbol)] {
+    static TABLE: OnceLock<Vec<(&'static str, Symbol)>> = OnceLock::new();
+    TABLE.get_or_init(|| iter_symbols().map(|s| (s.id(), s)).collect())
+}
+
+#[derive(Debug, Serialize)]
+pub struct SymbolInfo {
+    pub id: String,
+    pub command: &'static str,
+    pub package: &'static str,
+    pub font_encoding: &'static str,
+    pub text_mode: bool,
+    pub math_mode: bool,
+}
+
+impl SymbolInfo {
+    /// Takes the id of `symbol` rather than computing it as [`Symbol::id`] leaks a string on every
+    /// call
+    pub fn new(id: &str, symbol: Symbol) -> Self {
+        SymbolInfo {
+            id: id.to_string(),
+            command: symbol.command,
+            package: symbol.package,
+            font_encoding: symbol.font_encoding,
+            text_mode: symbol.text_mode,
+            math_mode: symbol.math_mode,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_table() {
+        assert_eq!(table().len(), iter_symbols().count());
+        for (id, symbol) in table() {
+            assert_eq!(Symbol::from_id(id).as_ref(), Some(symbol));
+        }
+    }
+}
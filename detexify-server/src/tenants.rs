@@ -0,0 +1,185 @@
+use crate::store::Store;
+use detexify::Classifier;
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+
+/// Maximum length of a classifier name
+pub const MAX_NAME_LENGTH: usize = 64;
+
+/// Named classifiers holding only their own samples, classification layers them on top of the
+/// server's classifier. Persisted to one data directory per classifier when the server has a
+/// data directory.
+pub struct Tenants {
+    tenants: RwLock<HashMap<String, Arc<Store>>>,
+    dir: Option<PathBuf>,
+    temperature: Option<f64>,
+}
+
+#[derive(Debug)]
+pub enum TenantError {
+    InvalidName,
+    Exists,
+    Io(io::Error),
+}
+
+impl From<io::Error> for TenantError {
+    fn from(e: io::Error) -> Self {
+        TenantError::Io(e)
+    }
+}
+
+/// Names are used as directory names so are restricted to lowercase ascii letters, digits, `-`
+/// and `_`
+pub fn valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= MAX_NAME_LENGTH
+        && name
+            .bytes()
+            .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'-' || b == b'_')
+}
+
+impl Tenants {
+    pub fn in_memory() -> Tenants {
+        Tenants {
+            tenants: RwLock::new(HashMap::new()),
+            dir: None,
+            temperature: None,
+        }
+    }
+
+    /// Opens every classifier stored in `dir`
+    pub fn open(dir: PathBuf) -> io::Result<Tenants> {
+        fs::create_dir_all(&dir)?;
+
+        let mut tenants = HashMap::new();
+        for entry in fs::read_dir(&dir)? {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if entry.file_type()?.is_dir() && valid_name(&name) {
+                let store = Store::open(entry.path(), || Ok(Classifier::new()))?;
+                tenants.insert(name, Arc::new(store));
+            }
+        }
+
+        Ok(Tenants {
+            tenants: RwLock::new(tenants),
+            dir: Some(dir),
+            temperature: None,
+        })
+    }
+
+    /// Sets the softmax temperature of every classifier, including those created later
+    pub fn with_temperature(mut self, temperature: f64) -> Tenants {
+        for store in self.tenants.get_mut().unwrap().values() {
+            store.set_temperature(temperature);
+        }
+        self.temperature = Some(temperature);
+        self
+    }
+
+    pub fn get(&self, name: &str) -> Option<Arc<Store>> {
+        self.tenants.read().unwrap().get(name).cloned()
+    }
+
+    /// Names of every classifier, sorted
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.tenants.read().unwrap().keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    /// Creates an empty classifier named `name`
+    pub fn create(&self, name: &str) -> Result<Arc<Store>, TenantError> {
+        if !valid_name(name) {
+            return Err(TenantError::InvalidName);
+        }
+
+        let mut tenants = self.tenants.write().unwrap();
+        if tenants.contains_key(name) {
+            return Err(TenantError::Exists);
+        }
+
+        let store = match &self.dir {
+            Some(dir) => Store::open(dir.join(name), || Ok(Classifier::new()))?,
+            None => Store::in_memory(Classifier::new()),
+        };
+        if let Some(temperature) = self.temperature {
+            store.set_temperature(temperature);
+        }
+        let store = Arc::new(store);
+        tenants.insert(name.to_string(), store.clone());
+
+        Ok(store)
+    }
+
+    /// Deletes the classifier named `name` and its data, returns `false` if there is none
+    pub fn delete(&self, name: &str) -> io::Result<bool> {
+        let mut tenants = self.tenants.write().unwrap();
+        if tenants.remove(name).is_none() {
+            return Ok(false);
+        }
+
+        if let Some(dir) = &self.dir {
+            fs::remove_dir_all(dir.join(name))?;
+        }
+
+        Ok(true)
+    }
+
+    /// Snapshots every classifier, see [`Store::snapshot`]
+    pub fn snapshot(&self) -> io::Result<()> {
+        let stores: Vec<Arc<Store>> = self.tenants.read().unwrap().values().cloned().collect();
+        for store in stores {
+            store.snapshot()?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use detexify::{Point, Stroke, StrokeSample};
+
+    #[test]
+    fn test_valid_name() {
+        assert!(valid_name("team-a_2"));
+        assert!(!valid_name(""));
+        assert!(!valid_name("Team"));
+        assert!(!valid_name(".."));
+        assert!(!valid_name(&"a".repeat(MAX_NAME_LENGTH + 1)));
+    }
+
+    #[test]
+    fn test_tenants() {
+        let dir = std::env::temp_dir().join(format!("detexify-tenants-{}", std::process::id()));
+        let sample = StrokeSample::new(vec![Stroke::new(vec![
+            Point { x: 0.0, y: 0.0 },
+            Point { x: 1.0, y: 1.0 },
+        ])])
+        .unwrap();
+
+        let tenants = Tenants::open(dir.clone()).unwrap();
+        let store = tenants.create("team").unwrap();
+        store.train("diagonal".to_string(), sample).unwrap();
+        assert!(matches!(tenants.create("team"), Err(TenantError::Exists)));
+        assert!(matches!(
+            tenants.create("../x"),
+            Err(TenantError::InvalidName)
+        ));
+        drop(tenants);
+
+        let tenants = Tenants::open(dir.clone()).unwrap();
+        assert_eq!(tenants.names(), vec!["team"]);
+        assert_eq!(tenants.get("team").unwrap().read().sample_count(), 1);
+        assert!(tenants.delete("team").unwrap());
+        assert!(!tenants.delete("team").unwrap());
+        assert!(!dir.join("team").exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
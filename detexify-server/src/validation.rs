@@ -0,0 +1,160 @@
+use crate::error::{ApiError, ErrorCode};
+use axum::http::StatusCode;
+use detexify::{Point, Stroke, StrokeSample};
+
+/// Maximum number of samples in a batch request
+pub const MAX_BATCH_SIZE: usize = 1_000;
+
+/// Maximum absolute value of a coordinate, larger values overflow the distance computations
+pub const MAX_COORDINATE: f64 = 1e6;
+
+/// Bounds on the size of a sample
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Limits {
+    pub strokes: usize,
+    pub points_per_stroke: usize,
+    /// Points summed over all strokes
+    pub points: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            strokes: 100,
+            points_per_stroke: 5_000,
+            points: 10_000,
+        }
+    }
+}
+
+/// Validates the strokes of a request and builds a sample from them
+pub fn sample_from_strokes(
+    strokes: Vec<Stroke>,
+    limits: &Limits,
+) -> Result<StrokeSample, ApiError> {
+    check_stroke_count(strokes.len(), limits)?;
+    for stroke in &strokes {
+        check_stroke_points(stroke.points().count(), limits)?;
+    }
+    check_point_count(strokes.iter().map(|s| s.points().count()).sum(), limits)?;
+    strokes
+        .iter()
+        .flat_map(|s| s.points())
+        .try_for_each(check_point)?;
+
+    StrokeSample::new(strokes).ok_or_else(|| {
+        ApiError::new(
+            StatusCode::UNPROCESSABLE_ENTITY,
+            ErrorCode::EmptySample,
+            "sample has no points",
+        )
+    })
+}
+
+/// Checks a sample with `strokes` strokes is within the limits
+pub fn check_stroke_count(strokes: usize, limits: &Limits) -> Result<(), ApiError> {
+    if strokes > limits.strokes {
+        return Err(ApiError::new(
+            StatusCode::PAYLOAD_TOO_LARGE,
+            ErrorCode::TooManyStrokes,
+            format!(
+                "sample has {} strokes, the limit is {}",
+                strokes, limits.strokes
+            ),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Checks a stroke with `points` points is within the limits
+pub fn check_stroke_points(points: usize, limits: &Limits) -> Result<(), ApiError> {
+    if points > limits.points_per_stroke {
+        return Err(ApiError::new(
+            StatusCode::PAYLOAD_TOO_LARGE,
+            ErrorCode::TooManyPoints,
+            format!(
+                "stroke has {} points, the limit is {}",
+                points, limits.points_per_stroke
+            ),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Checks a sample with `points` points in total is within the limits
+pub fn check_point_count(points: usize, limits: &Limits) -> Result<(), ApiError> {
+    if points > limits.points {
+        return Err(ApiError::new(
+            StatusCode::PAYLOAD_TOO_LARGE,
+            ErrorCode::TooManyPoints,
+            format!(
+                "sample has {} points, the limit is {}",
+                points, limits.points
+            ),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Checks both coordinates of `point` are finite and within [`MAX_COORDINATE`]
+pub fn check_point(point: &Point) -> Result<(), ApiError> {
+    let valid = |c: f64| c.is_finite() && c.abs() <= MAX_COORDINATE;
+    if !(valid(point.x) && valid(point.y)) {
+        return Err(ApiError::new(
+            StatusCode::UNPROCESSABLE_ENTITY,
+            ErrorCode::InvalidCoordinates,
+            format!(
+                "coordinates must be finite numbers of at most {} in magnitude",
+                MAX_COORDINATE
+            ),
+        ));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stroke(points: usize) -> Stroke {
+        Stroke::new(
+            (0..points)
+                .map(|i| Point {
+                    x: i as f64,
+                    y: 0.0,
+                })
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn test_limits() {
+        let limits = Limits {
+            strokes: 2,
+            points_per_stroke: 3,
+            points: 5,
+        };
+        let code =
+            |strokes: Vec<Stroke>| sample_from_strokes(strokes, &limits).err().map(|e| e.code);
+
+        assert_eq!(code(vec![stroke(3), stroke(2)]), None);
+        assert_eq!(
+            code(vec![stroke(1), stroke(1), stroke(1)]),
+            Some(ErrorCode::TooManyStrokes)
+        );
+        assert_eq!(code(vec![stroke(4)]), Some(ErrorCode::TooManyPoints));
+        assert_eq!(
+            code(vec![stroke(3), stroke(3)]),
+            Some(ErrorCode::TooManyPoints)
+        );
+        assert_eq!(
+            code(vec![Stroke::new(vec![Point { x: 1e9, y: 0.0 }])]),
+            Some(ErrorCode::InvalidCoordinates)
+        );
+        assert_eq!(code(vec![]), Some(ErrorCode::EmptySample));
+    }
+}
@@ -0,0 +1,81 @@
+use crate::error::ApiError;
+use std::sync::Arc;
+use std::thread;
+use tokio::sync::Semaphore;
+use tokio::task;
+
+/// Bounds how much CPU heavy or blocking work, mostly classification, runs at once. Tokio's
+/// blocking pool grows up to 512 threads, so this is what the `workers` setting limits it by.
+#[derive(Clone)]
+pub struct Workers {
+    permits: Arc<Semaphore>,
+}
+
+impl Workers {
+    /// Runs at most `workers` jobs at once
+    pub fn new(workers: usize) -> Workers {
+        Workers {
+            permits: Arc::new(Semaphore::new(workers)),
+        }
+    }
+
+    /// Runs `f` on the blocking pool once fewer than `workers` jobs are running
+    pub(crate) async fn run<T, F>(self, f: F) -> Result<T, ApiError>
+    where
+        F: FnOnce() -> Result<T, ApiError> + Send + 'static,
+        T: Send + 'static,
+    {
+        let permit = self
+            .permits
+            .acquire_owned()
+            .await
+            .map_err(|e| ApiError::internal("workers closed", e))?;
+
+        // the permit is held until `f` returns, even if the request is dropped first
+        task::spawn_blocking(move || {
+            let _permit = permit;
+            f()
+        })
+        .await
+        .map_err(|e| ApiError::internal("worker failed", e))?
+    }
+}
+
+impl Default for Workers {
+    /// One worker per CPU
+    fn default() -> Workers {
+        Workers::new(thread::available_parallelism().map_or(1, |n| n.get()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_workers() {
+        let workers = Workers::new(2);
+        let running = Arc::new(AtomicUsize::new(0));
+        let most = Arc::new(AtomicUsize::new(0));
+
+        let jobs: Vec<_> = (0..8)
+            .map(|_| {
+                let (running, most) = (running.clone(), most.clone());
+                tokio::spawn(workers.clone().run(move || {
+                    let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                    most.fetch_max(now, Ordering::SeqCst);
+                    thread::sleep(Duration::from_millis(20));
+                    running.fetch_sub(1, Ordering::SeqCst);
+                    Ok(())
+                }))
+            })
+            .collect();
+        for job in jobs {
+            job.await.unwrap().unwrap();
+        }
+
+        assert_eq!(most.load(Ordering::SeqCst), 2);
+    }
+}
@@ -0,0 +1,1085 @@
+use detexify::{base32_to_base64, iter_symbols, Classifier, Point, Stroke, StrokeSample};
+use detexify_server::feedback::{self, FeedbackLog};
+use detexify_server::metrics::Metrics;
+use detexify_server::rate_limit::RateLimiter;
+use detexify_server::store::Store;
+use detexify_server::tenants::Tenants;
+use detexify_server::validation::{Limits, MAX_BATCH_SIZE};
+use detexify_server::workers::Workers;
+use detexify_server::{app, AppState};
+use serde_json::{json, Value};
+use std::net::SocketAddr;
+use std::num::NonZeroU32;
+use std::sync::Arc;
+
+fn symbol_id(command: &str) -> String {
+    iter_symbols()
+        .find(|s| s.command == command)
+        .unwrap()
+        .id()
+        .to_string()
+}
+
+fn line(from: (f64, f64), to: (f64, f64)) -> Vec<Stroke> {
+    vec![Stroke::new(vec![
+        Point {
+            x: from.0,
+            y: from.1,
+        },
+        Point {
+            x: (from.0 + to.0) / 2.0,
+            y: (from.1 + to.1) / 2.0,
+        },
+        Point { x: to.0, y: to.1 },
+    ])]
+}
+
+/// State with a classifier that knows a horizontal and a vertical line
+fn state() -> AppState {
+    let mut classifier = Classifier::new();
+    for (command, strokes) in [
+        ("\\textendash", line((0.0, 0.0), (100.0, 0.0))),
+        ("\\textbar", line((0.0, 0.0), (0.0, 100.0))),
+    ] {
+        let sample = StrokeSample::new(strokes).unwrap();
+        classifier.train(symbol_id(command), sample);
+    }
+
+    AppState {
+        store: Arc::new(Store::in_memory(classifier)),
+        snapshot_token: None,
+        symbols: None,
+        metrics: Metrics::new(),
+        limits: Limits::default(),
+        max_request_size: 64 * 1024,
+        rate_limiter: None,
+        feedback: None,
+        tenants: Tenants::in_memory(),
+        workers: Workers::new(2),
+    }
+}
+
+/// Starts a server on a free local port, returning its base url
+async fn serve(state: AppState) -> String {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    let app = app(Arc::new(state)).into_make_service_with_connect_info::<SocketAddr>();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+    format!("http://{}", address)
+}
+
+async fn spawn() -> String {
+    serve(state()).await
+}
+
+#[tokio::test]
+async fn test_root() {
+    let url = spawn().await;
+
+    let response = reqwest::get(&url).await.unwrap();
+    assert_eq!(response.status(), 200);
+
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["server"], "rust detexify server");
+    assert_eq!(body["version"], env!("CARGO_PKG_VERSION"));
+
+    let response = reqwest::get(format!("{}/openapi.json", url)).await.unwrap();
+    assert_eq!(response.status(), 200);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["info"]["version"], env!("CARGO_PKG_VERSION"));
+    assert!(body["paths"]["/classify"]["post"].is_object());
+}
+
+/// Every documented operation is routed and every other method of a documented path isn't
+#[tokio::test]
+async fn test_openapi_matches_routes() {
+    let url = spawn().await;
+    let client = reqwest::Client::new();
+
+    let document: Value = reqwest::get(format!("{}/openapi.json", url))
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let paths = document["paths"].as_object().unwrap();
+    assert!(!paths.is_empty());
+
+    for (path, operations) in paths {
+        let concrete = path
+            .replace("{name}", "team")
+            .replace("{id}", &symbol_id("\\textendash"));
+        for method in ["get", "post", "put", "delete", "patch"] {
+            let response = client
+                .request(
+                    method.to_uppercase().parse().unwrap(),
+                    format!("{}{}", url, concrete),
+                )
+                .send()
+                .await
+                .unwrap();
+            let status = response.status();
+            // handlers return 404 for unknown classifiers, only the fallbacks use these codes
+            let code = match status.as_u16() {
+                404 | 405 => response.json::<Value>().await.unwrap()["error"]["code"].clone(),
+                _ => Value::Null,
+            };
+            let routed = code != "not_found" && code != "method_not_allowed";
+
+            assert_eq!(
+                routed,
+                operations.get(method).is_some(),
+                "{} {} returned {}",
+                method,
+                path,
+                status
+            );
+        }
+    }
+}
+
+/// Checks `value` against `schema` from `document`, in the subset of JSON Schema the document
+/// uses. Objects listing `properties` are closed unless `additionalProperties` is set.
+fn check_schema(document: &Value, schema: &Value, value: &Value) -> Result<(), String> {
+    if let Some(reference) = schema["$ref"].as_str() {
+        let name = reference.trim_start_matches("#/components/schemas/");
+        return check_schema(document, &document["components"]["schemas"][name], value);
+    }
+    if value.is_null() && schema["nullable"] == true {
+        return Ok(());
+    }
+    for schema in schema["allOf"].as_array().into_iter().flatten() {
+        check_schema(document, schema, value)?;
+    }
+    if let Some(schemas) = schema["oneOf"].as_array() {
+        let matching = schemas
+            .iter()
+            .filter(|schema| check_schema(document, schema, value).is_ok())
+            .count();
+        if matching != 1 {
+            return Err(format!("{} matches {} of {}", value, matching, schema));
+        }
+    }
+
+    let typed = match schema["type"].as_str() {
+        Some("object") => value.is_object(),
+        Some("array") => value.is_array(),
+        Some("string") => value.is_string(),
+        Some("number") => value.is_number(),
+        Some("integer") => value.is_u64() || value.is_i64(),
+        Some("boolean") => value.is_boolean(),
+        _ => true,
+    };
+    if !typed {
+        return Err(format!("{} isn't of type {}", value, schema["type"]));
+    }
+    if let Some(values) = schema["enum"].as_array() {
+        if !values.contains(value) {
+            return Err(format!("{} isn't one of {:?}", value, values));
+        }
+    }
+    if let (Some(value), Some(minimum)) = (value.as_f64(), schema["minimum"].as_f64()) {
+        if value < minimum {
+            return Err(format!("{} is below {}", value, minimum));
+        }
+    }
+    if let (Some(value), Some(maximum)) = (value.as_f64(), schema["maximum"].as_f64()) {
+        if value > maximum {
+            return Err(format!("{} is above {}", value, maximum));
+        }
+    }
+
+    if let Some(items) = value.as_array() {
+        for item in items {
+            check_schema(document, &schema["items"], item)?;
+        }
+    }
+    if let Some(object) = value.as_object() {
+        for required in schema["required"].as_array().into_iter().flatten() {
+            if !object.contains_key(required.as_str().unwrap()) {
+                return Err(format!("{} is missing {}", value, required));
+            }
+        }
+        for (key, field) in object {
+            match (
+                schema["properties"].get(key),
+                schema.get("additionalProperties"),
+            ) {
+                (Some(property), _) => check_schema(document, property, field)?,
+                (None, Some(additional)) => check_schema(document, additional, field)?,
+                (None, None) if schema.get("properties").is_some() => {
+                    return Err(format!("{} has the undocumented field {}", value, key));
+                }
+                (None, None) => {}
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Checks the status and JSON body of a response against the operation documenting it
+async fn check_response(
+    document: &Value,
+    method: &str,
+    path: &str,
+    response: reqwest::Response,
+    status: u16,
+) {
+    assert_eq!(response.status(), status, "{} {}", method, path);
+    let documented = &document["paths"][path][method]["responses"][status.to_string()];
+    assert!(
+        documented.is_object(),
+        "{} {} doesn't document {}",
+        method,
+        path,
+        status
+    );
+
+    let schema = &documented["content"]["application/json"]["schema"];
+    if !schema.is_null() {
+        let body: Value = response.json().await.unwrap();
+        if let Err(e) = check_schema(document, schema, &body) {
+            panic!("{} {} {}: {}", method, path, status, e);
+        }
+    }
+}
+
+/// Responses of every operation match the schemas the document gives them
+#[tokio::test]
+async fn test_openapi_schemas() {
+    let authorized = AppState {
+        snapshot_token: Some("secret".to_string()),
+        ..state()
+    };
+    let store = authorized.store.clone();
+    let url = serve(authorized).await;
+    let client = reqwest::Client::new();
+    let document: Value = reqwest::get(format!("{}/openapi.json", url))
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+
+    let strokes = json!(line((10.0, 50.0), (90.0, 52.0)));
+    let dash = symbol_id("\\textendash");
+    let form = format!("strokes={}", strokes);
+    let hs_train = format!(
+        "id={}&{}",
+        form_encode(&base32_to_base64(&dash).unwrap()),
+        form
+    );
+    let train = format!("/train/{}", dash);
+    let team_train = format!("/c/team/train/{}", dash);
+    // method, documented path, path, JSON body, expected status
+    let cases: Vec<(&str, &str, &str, Option<Value>, u16)> = vec![
+        ("get", "/", "/", None, 200),
+        ("get", "/healthz", "/healthz", None, 200),
+        ("get", "/readyz", "/readyz", None, 200),
+        ("get", "/metrics", "/metrics", None, 200),
+        ("get", "/openapi.json", "/openapi.json", None, 200),
+        ("post", "/classify", "/classify", Some(strokes.clone()), 200),
+        (
+            "post",
+            "/classify",
+            "/classify?reject=true",
+            Some(strokes.clone()),
+            403,
+        ),
+        (
+            "post",
+            "/classify",
+            "/classify?limit=x",
+            Some(strokes.clone()),
+            400,
+        ),
+        ("post", "/classify", "/classify", Some(json!([])), 422),
+        (
+            "post",
+            "/classify/batch",
+            "/classify/batch",
+            Some(json!([strokes])),
+            200,
+        ),
+        ("post", "/train/{id}", &train, Some(strokes.clone()), 200),
+        (
+            "post",
+            "/train/{id}",
+            "/train/nope",
+            Some(strokes.clone()),
+            404,
+        ),
+        ("post", "/hs/classify", "/hs/classify", None, 200),
+        ("post", "/hs/train", "/hs/train", None, 200),
+        (
+            "post",
+            "/feedback",
+            "/feedback",
+            Some(json!({ "strokes": strokes, "ranking": [&dash], "chosen": &dash })),
+            403,
+        ),
+        ("get", "/symbols", "/symbols?q=textbar", None, 200),
+        ("get", "/symbols", "/symbols?mode=neither", None, 400),
+        ("get", "/snapshot", "/snapshot?encoding=base64", None, 200),
+        ("put", "/snapshot", "/snapshot", Some(json!([])), 400),
+        ("get", "/c", "/c", None, 200),
+        ("put", "/c/{name}", "/c/team", None, 201),
+        ("put", "/c/{name}", "/c/team", None, 409),
+        ("get", "/c/{name}", "/c/team", None, 200),
+        (
+            "post",
+            "/c/{name}/classify",
+            "/c/team/classify",
+            Some(strokes.clone()),
+            200,
+        ),
+        (
+            "post",
+            "/c/{name}/train/{id}",
+            &team_train,
+            Some(strokes.clone()),
+            200,
+        ),
+        ("delete", "/c/{name}", "/c/team", None, 200),
+        ("get", "/c/{name}", "/c/team", None, 404),
+    ];
+
+    for (method, path, concrete, body, status) in cases {
+        let mut request = client
+            .request(
+                method.to_uppercase().parse().unwrap(),
+                format!("{}{}", url, concrete),
+            )
+            .bearer_auth("secret");
+        request = match body {
+            Some(body) => request.json(&body),
+            None if path.starts_with("/hs/") => request
+                .header("Content-Type", "application/x-www-form-urlencoded")
+                .body(match path {
+                    "/hs/train" => hs_train.clone(),
+                    _ => form.clone(),
+                }),
+            None => request,
+        };
+        check_response(
+            &document,
+            method,
+            path,
+            request.send().await.unwrap(),
+            status,
+        )
+        .await;
+    }
+
+    // unauthorized uploads and the envelope returned once rejection is enabled
+    let response = client
+        .put(format!("{}/snapshot", url))
+        .json(&json!({}))
+        .send()
+        .await
+        .unwrap();
+    check_response(&document, "put", "/snapshot", response, 401).await;
+    store.learn_thresholds(1.0);
+    let response = client
+        .post(format!("{}/classify?reject=true", url))
+        .json(&strokes)
+        .send()
+        .await
+        .unwrap();
+    check_response(&document, "post", "/classify", response, 200).await;
+
+    // the rate limit
+    let url = serve(AppState {
+        rate_limiter: Some(RateLimiter::new(
+            NonZeroU32::new(1).unwrap(),
+            NonZeroU32::new(1).unwrap(),
+        )),
+        ..state()
+    })
+    .await;
+    reqwest::get(&url).await.unwrap();
+    check_response(
+        &document,
+        "get",
+        "/",
+        reqwest::get(&url).await.unwrap(),
+        429,
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn test_health_and_metrics() {
+    let url = spawn().await;
+    let client = reqwest::Client::new();
+
+    let response = client.get(format!("{}/healthz", url)).send().await.unwrap();
+    assert_eq!(response.status(), 200);
+
+    let response = client.get(format!("{}/readyz", url)).send().await.unwrap();
+    assert_eq!(response.status(), 200);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["samples"], 2);
+
+    client
+        .post(format!("{}/classify", url))
+        .json(&line((10.0, 50.0), (90.0, 52.0)))
+        .send()
+        .await
+        .unwrap();
+
+    let response = client.get(format!("{}/metrics", url)).send().await.unwrap();
+    assert_eq!(response.status(), 200);
+    let metrics = response.text().await.unwrap();
+    assert!(metrics.contains(
+        "detexify_http_requests_total{method=\"POST\",route=\"/classify\",status=\"200\"} 1"
+    ));
+    assert!(metrics.contains("detexify_classify_duration_seconds_count{route=\"/classify\"} 1"));
+    assert!(metrics.contains("detexify_classifier_symbols 2"));
+}
+
+#[tokio::test]
+async fn test_classify() {
+    let url = spawn().await;
+    let client = reqwest::Client::new();
+
+    let response = client
+        .post(format!("{}/classify", url))
+        .json(&line((10.0, 50.0), (90.0, 52.0)))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+
+    let body: Value = response.json().await.unwrap();
+    let results = body.as_array().unwrap();
+    assert_eq!(results.len(), 2);
+    assert_eq!(results[0]["id"], symbol_id("\\textendash").as_str());
+    assert_eq!(results[0]["symbol"]["command"], "\\textendash");
+    assert!(results[0]["score"].as_f64().unwrap() <= results[1]["score"].as_f64().unwrap());
+
+    let response = client
+        .post(format!("{}/classify?limit=1", url))
+        .json(&json!({ "strokes": line((50.0, 0.0), (52.0, 90.0)) }))
+        .send()
+        .await
+        .unwrap();
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body.as_array().unwrap().len(), 1);
+    assert_eq!(body[0]["symbol"]["command"], "\\textbar");
+}
+
+#[tokio::test]
+async fn test_reject() {
+    let client = reqwest::Client::new();
+    let horizontal = line((10.0, 50.0), (90.0, 52.0));
+
+    let url = spawn().await;
+    let response = client
+        .post(format!("{}/classify?reject=true", url))
+        .json(&horizontal)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 403);
+
+    let state = state();
+    for (command, strokes) in [
+        ("\\textendash", line((0.0, 10.0), (100.0, 14.0))),
+        ("\\textbar", line((0.0, 0.0), (4.0, 100.0))),
+    ] {
+        let sample = StrokeSample::new(strokes).unwrap();
+        state.store.train(symbol_id(command), sample).unwrap();
+    }
+    state.store.learn_thresholds(1.0);
+    let url = serve(state).await;
+
+    let scribble = vec![Stroke::new(
+        (0..40)
+            .map(|i| Point {
+                x: (i % 2) as f64 * 100.0,
+                y: i as f64 * 2.5,
+            })
+            .collect(),
+    )];
+    for (strokes, unknown) in [(horizontal, false), (scribble, true)] {
+        let response = client
+            .post(format!("{}/classify", url))
+            .json(&json!({ "strokes": strokes, "reject": true }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+        let body: Value = response.json().await.unwrap();
+        assert_eq!(body["unknown"], unknown);
+        assert_eq!(body["results"].as_array().unwrap().len(), 2);
+    }
+}
+
+#[tokio::test]
+async fn test_classify_batch() {
+    let url = spawn().await;
+    let client = reqwest::Client::new();
+
+    // results are in the order of the samples, envelopes override the query parameters
+    let response = client
+        .post(format!("{}/classify/batch?limit=2", url))
+        .json(&json!([
+            line((50.0, 0.0), (52.0, 90.0)),
+            { "strokes": line((10.0, 50.0), (90.0, 52.0)), "limit": 1 },
+        ]))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body.as_array().unwrap().len(), 2);
+    assert_eq!(body[0].as_array().unwrap().len(), 2);
+    assert_eq!(body[0][0]["symbol"]["command"], "\\textbar");
+    assert_eq!(body[1].as_array().unwrap().len(), 1);
+    assert_eq!(body[1][0]["symbol"]["command"], "\\textendash");
+
+    let response = client
+        .post(format!("{}/classify/batch", url))
+        .json(&json!([line((0.0, 0.0), (1.0, 1.0)), []]))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 422);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["error"]["code"], "empty_sample");
+    assert!(body["error"]["message"]
+        .as_str()
+        .unwrap()
+        .starts_with("sample 1: "));
+
+    let samples = vec![line((0.0, 0.0), (1.0, 1.0)); MAX_BATCH_SIZE + 1];
+    let response = client
+        .post(format!("{}/classify/batch", url))
+        .json(&samples)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 413);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["error"]["code"], "too_many_samples");
+}
+
+#[tokio::test]
+async fn test_hs_classify() {
+    let url = spawn().await;
+    let client = reqwest::Client::new();
+
+    // the JSON has no characters that need escaping in a form
+    let strokes = serde_json::to_string(&line((10.0, 50.0), (90.0, 52.0))).unwrap();
+    let response = client
+        .post(format!("{}/hs/classify", url))
+        .header("Content-Type", "application/x-www-form-urlencoded")
+        .body(format!("strokes={}", strokes))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+
+    // ids are base64 like the Haskell backend's
+    let id = base32_to_base64(&symbol_id("\\textendash")).unwrap();
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body[0]["id"], id.as_str());
+    assert_eq!(body[0]["symbol"]["id"], id.as_str());
+    assert!(body[0]["score"].is_f64());
+    assert_eq!(body[0]["symbol"]["command"], "\\textendash");
+    assert_eq!(body[0]["symbol"]["mathmode"], false);
+
+    let response = client
+        .post(format!("{}/hs/classify", url))
+        .header("Content-Type", "application/x-www-form-urlencoded")
+        .body("strokes=[[")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 400);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["error"]["code"], "bad_json");
+}
+
+/// Percent-encodes the characters of base64 ids that mean something in a form
+fn form_encode(value: &str) -> String {
+    value
+        .replace('+', "%2B")
+        .replace('/', "%2F")
+        .replace('=', "%3D")
+}
+
+#[tokio::test]
+async fn test_hs_train() {
+    let url = spawn().await;
+    let client = reqwest::Client::new();
+    let strokes = serde_json::to_string(&line((50.0, 0.0), (52.0, 100.0))).unwrap();
+    let train = |id: String| {
+        client
+            .post(format!("{}/hs/train", url))
+            .header("Content-Type", "application/x-www-form-urlencoded")
+            .body(format!("id={}&strokes={}", form_encode(&id), strokes))
+            .send()
+    };
+
+    let bar = symbol_id("\\textbar");
+    let response = train(base32_to_base64(&bar).unwrap()).await.unwrap();
+    assert_eq!(response.status(), 200);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["message"], "Symbol was successfully trained.");
+
+    let snapshot: Value = reqwest::get(format!("{}/snapshot", url))
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(snapshot[&bar].as_array().unwrap().len(), 2);
+
+    let response = train("bm9wZQ==".to_string()).await.unwrap();
+    assert_eq!(response.status(), 404);
+}
+
+#[tokio::test]
+async fn test_classify_errors() {
+    let url = spawn().await;
+    let client = reqwest::Client::new();
+
+    let response = client
+        .post(format!("{}/classify", url))
+        .json(&json!([[]]))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 422);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["error"]["code"], "empty_sample");
+
+    let response = client
+        .post(format!("{}/classify", url))
+        .header("Content-Type", "application/json")
+        .body("[[{\"x\": 1")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 400);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["error"]["code"], "bad_json");
+
+    let response = client
+        .post(format!("{}/classify?mode=both", url))
+        .json(&line((0.0, 0.0), (1.0, 1.0)))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 400);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["error"]["code"], "invalid_parameter");
+
+    let response = client
+        .post(format!("{}/classify", url))
+        .json(&json!([[{ "x": 1e300, "y": 0 }]]))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 422);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["error"]["code"], "invalid_coordinates");
+}
+
+#[tokio::test]
+async fn test_limits() {
+    let url = serve(AppState {
+        limits: Limits {
+            strokes: 1,
+            ..Limits::default()
+        },
+        rate_limiter: Some(RateLimiter::new(
+            NonZeroU32::new(1).unwrap(),
+            NonZeroU32::new(2).unwrap(),
+        )),
+        ..state()
+    })
+    .await;
+    let client = reqwest::Client::new();
+
+    let mut strokes = line((0.0, 0.0), (1.0, 1.0));
+    strokes.extend(line((1.0, 0.0), (0.0, 1.0)));
+    let response = client
+        .post(format!("{}/classify", url))
+        .json(&strokes)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 413);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["error"]["code"], "too_many_strokes");
+
+    let response = client.get(&url).send().await.unwrap();
+    assert_eq!(response.status(), 200);
+    let response = client.get(&url).send().await.unwrap();
+    assert_eq!(response.status(), 429);
+    assert!(response.headers().contains_key("retry-after"));
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["error"]["code"], "rate_limited");
+
+    let response = client.get(format!("{}/healthz", url)).send().await.unwrap();
+    assert_eq!(response.status(), 200);
+}
+
+#[tokio::test]
+async fn test_classify_ws() {
+    use futures_util::{SinkExt, StreamExt};
+    use tokio_tungstenite::tungstenite::Message;
+
+    let url = spawn().await.replacen("http", "ws", 1);
+    let (mut socket, _) = tokio_tungstenite::connect_async(format!("{}/classify/ws?limit=1", url))
+        .await
+        .unwrap();
+
+    for stroke in line((10.0, 50.0), (90.0, 52.0)) {
+        let points: Vec<_> = stroke.points().collect();
+        for point in points {
+            let message = json!({ "type": "point", "x": point.x, "y": point.y });
+            socket
+                .send(Message::text(message.to_string()))
+                .await
+                .unwrap();
+        }
+    }
+    socket
+        .send(Message::text(json!({ "type": "pen_up" }).to_string()))
+        .await
+        .unwrap();
+
+    let reply: Value = match socket.next().await.unwrap().unwrap() {
+        Message::Text(text) => serde_json::from_str(text.as_str()).unwrap(),
+        message => panic!("unexpected message {:?}", message),
+    };
+    assert_eq!(reply["type"], "results");
+    assert_eq!(reply["strokes"], 1);
+    assert_eq!(reply["results"].as_array().unwrap().len(), 1);
+    assert_eq!(reply["results"][0]["symbol"]["command"], "\\textendash");
+
+    socket
+        .send(Message::text("{\"type\": \"erase\"}"))
+        .await
+        .unwrap();
+    let reply: Value = match socket.next().await.unwrap().unwrap() {
+        Message::Text(text) => serde_json::from_str(text.as_str()).unwrap(),
+        message => panic!("unexpected message {:?}", message),
+    };
+    assert_eq!(reply["error"]["code"], "bad_json");
+
+    // replies are limited to 10 results without a limit
+    let state = state();
+    for symbol in iter_symbols().take(20) {
+        let sample = StrokeSample::new(line((0.0, 0.0), (100.0, 10.0))).unwrap();
+        state.store.train(symbol.id().to_string(), sample).unwrap();
+    }
+    let url = serve(state).await.replacen("http", "ws", 1);
+    let (mut socket, _) = tokio_tungstenite::connect_async(format!("{}/classify/ws", url))
+        .await
+        .unwrap();
+    let points = json!({ "type": "points", "points": line((10.0, 50.0), (90.0, 52.0))[0] });
+    socket
+        .send(Message::text(points.to_string()))
+        .await
+        .unwrap();
+    socket
+        .send(Message::text(json!({ "type": "pen_up" }).to_string()))
+        .await
+        .unwrap();
+    let reply: Value = match socket.next().await.unwrap().unwrap() {
+        Message::Text(text) => serde_json::from_str(text.as_str()).unwrap(),
+        message => panic!("unexpected message {:?}", message),
+    };
+    assert_eq!(reply["results"].as_array().unwrap().len(), 10);
+}
+
+#[tokio::test]
+async fn test_classify_ws_limits() {
+    use futures_util::{SinkExt, StreamExt};
+    use tokio_tungstenite::tungstenite::Message;
+
+    // the upgrade takes the first request of the burst, each pen-up another
+    let url = serve(AppState {
+        rate_limiter: Some(RateLimiter::new(
+            NonZeroU32::new(1).unwrap(),
+            NonZeroU32::new(2).unwrap(),
+        )),
+        ..state()
+    })
+    .await
+    .replacen("http", "ws", 1);
+    let (mut socket, _) = tokio_tungstenite::connect_async(format!("{}/classify/ws", url))
+        .await
+        .unwrap();
+
+    let mut replies = Vec::new();
+    for _ in 0..2 {
+        let points = json!({ "type": "points", "points": line((10.0, 50.0), (90.0, 52.0))[0] });
+        socket
+            .send(Message::text(points.to_string()))
+            .await
+            .unwrap();
+        socket
+            .send(Message::text(json!({ "type": "pen_up" }).to_string()))
+            .await
+            .unwrap();
+        let reply: Value = match socket.next().await.unwrap().unwrap() {
+            Message::Text(text) => serde_json::from_str(text.as_str()).unwrap(),
+            message => panic!("unexpected message {:?}", message),
+        };
+        replies.push(reply);
+    }
+    assert_eq!(replies[0]["type"], "results");
+    assert_eq!(replies[1]["error"]["code"], "rate_limited");
+
+    // messages over the request size limit close the connection
+    let url = spawn().await.replacen("http", "ws", 1);
+    let (mut socket, _) = tokio_tungstenite::connect_async(format!("{}/classify/ws", url))
+        .await
+        .unwrap();
+    socket
+        .send(Message::text(" ".repeat(128 * 1024)))
+        .await
+        .unwrap();
+    match socket.next().await {
+        Some(Ok(Message::Close(_))) | Some(Err(_)) | None => {}
+        message => panic!("unexpected message {:?}", message),
+    }
+}
+
+#[tokio::test]
+async fn test_feedback() {
+    let path = std::env::temp_dir().join(format!("detexify-feedback-{}.jsonl", std::process::id()));
+    let url = serve(AppState {
+        feedback: Some(FeedbackLog::open(&path).unwrap()),
+        ..state()
+    })
+    .await;
+    let client = reqwest::Client::new();
+
+    let strokes = line((10.0, 50.0), (90.0, 52.0));
+    let ranking = [symbol_id("\\textbar"), symbol_id("\\textendash")];
+    let response = client
+        .post(format!("{}/feedback", url))
+        .json(&json!({ "strokes": strokes, "ranking": ranking, "chosen": ranking[1] }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+
+    let response = client
+        .post(format!("{}/feedback", url))
+        .json(&json!({ "strokes": strokes, "ranking": ranking, "chosen": "nope" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 404);
+
+    let mut classifier = Classifier::new();
+    let dataset = std::fs::read(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    let stats = feedback::export(&dataset[..], &mut classifier).unwrap();
+    assert_eq!(stats.exported, 1);
+    assert_eq!(stats.top1, 0);
+
+    let response = client
+        .post(format!("{}/feedback", spawn().await))
+        .json(&json!({ "strokes": strokes, "ranking": ranking, "chosen": ranking[1] }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 403);
+}
+
+#[tokio::test]
+async fn test_snapshot() {
+    let url = spawn().await;
+    let client = reqwest::Client::new();
+
+    let response = client
+        .put(format!("{}/snapshot", url))
+        .body("{}")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 403);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["error"]["code"], "forbidden");
+
+    let url = serve(AppState {
+        snapshot_token: Some("secret".to_string()),
+        ..state()
+    })
+    .await;
+
+    for token in [None, Some("wrong"), Some("secret2")] {
+        let mut request = client.put(format!("{}/snapshot", url)).body("{}");
+        if let Some(token) = token {
+            request = request.bearer_auth(token);
+        }
+        let response = request.send().await.unwrap();
+        assert_eq!(response.status(), 401);
+        let body: Value = response.json().await.unwrap();
+        assert_eq!(body["error"]["code"], "unauthorized");
+    }
+
+    let response = client
+        .get(format!("{}/snapshot", url))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    let snapshot: Value = response.json().await.unwrap();
+    let dash = symbol_id("\\textendash");
+    assert_eq!(snapshot.as_object().unwrap().len(), 2);
+
+    // upload only the dash, with the base64 id of the original detexify snapshots
+    let upload = json!({ base32_to_base64(&dash).unwrap(): snapshot[&dash] });
+    let response = client
+        .put(format!("{}/snapshot", url))
+        .bearer_auth("secret")
+        .json(&upload)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+
+    let snapshot: Value = client
+        .get(format!("{}/snapshot", url))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(snapshot.as_object().unwrap().len(), 1);
+    assert_eq!(snapshot[&dash], upload[base32_to_base64(&dash).unwrap()]);
+
+    let snapshot: Value = client
+        .get(format!("{}/snapshot?encoding=base64", url))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(snapshot, upload);
+
+    let response = client
+        .get(format!("{}/snapshot?encoding=base16", url))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 400);
+
+    let response = client
+        .put(format!("{}/snapshot", url))
+        .bearer_auth("secret")
+        .body("[")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 400);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["error"]["code"], "invalid_snapshot");
+}
+
+#[tokio::test]
+async fn test_classifiers() {
+    let url = serve(AppState {
+        snapshot_token: Some("secret".to_string()),
+        ..state()
+    })
+    .await;
+    let client = reqwest::Client::new();
+
+    let response = client.put(format!("{}/c/team", url)).send().await.unwrap();
+    assert_eq!(response.status(), 401);
+
+    let response = client
+        .put(format!("{}/c/team", url))
+        .bearer_auth("secret")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 201);
+
+    let response = client
+        .put(format!("{}/c/team", url))
+        .bearer_auth("secret")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 409);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["error"]["code"], "classifier_exists");
+
+    // train the team's classifier to read a vertical line as a dash
+    let response = client
+        .post(format!(
+            "{}/c/team/train/{}",
+            url,
+            symbol_id("\\textendash")
+        ))
+        .json(&line((0.0, 0.0), (0.0, 100.0)))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+
+    let vertical = line((50.0, 0.0), (50.0, 90.0));
+    let dash_score = |body: Value| {
+        body.as_array()
+            .unwrap()
+            .iter()
+            .find(|r| r["symbol"]["command"] == "\\textendash")
+            .unwrap()["score"]
+            .as_f64()
+            .unwrap()
+    };
+    let mut scores = Vec::new();
+    for path in ["c/team/classify", "classify"] {
+        let response = client
+            .post(format!("{}/{}", url, path))
+            .json(&vertical)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+        scores.push(dash_score(response.json().await.unwrap()));
+    }
+    // the team's sample brings the dash closer without changing the shared classifier
+    assert!(scores[0] < scores[1]);
+
+    let response = client.get(format!("{}/c", url)).send().await.unwrap();
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(
+        body,
+        json!([{ "name": "team", "symbols": 1, "samples": 1 }])
+    );
+
+    let response = client
+        .delete(format!("{}/c/team", url))
+        .bearer_auth("secret")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+
+    let response = client
+        .post(format!("{}/c/team/classify", url))
+        .json(&vertical)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 404);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["error"]["code"], "unknown_classifier");
+}
@@ -0,0 +1,117 @@
+use detexify::evaluation::{cross_validate, Folds};
+use detexify::{to_base32_ids, Aggregation, StrokeSample};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::BufReader;
+use std::process;
+
+const USAGE: &str = "usage: detexify-evaluate <snapshot.json> [--folds <n>] [--k <k>] \
+[--aggregation mean|min|weighted] [--json] [--confusion <out.csv|out.json>]";
+
+fn fail(message: &str) -> ! {
+    eprintln!("{}\n{}", message, USAGE);
+    process::exit(2)
+}
+
+fn main() {
+    let mut snapshot = None;
+    let mut folds = Folds::LeaveOneOut;
+    let mut k = 2;
+    let mut aggregation = Aggregation::Mean;
+    let mut json = false;
+    let mut confusion = None;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .unwrap_or_else(|| fail(&format!("missing value for {}", arg)))
+        };
+
+        match arg.as_str() {
+            "--folds" => {
+                folds = match value().parse() {
+                    Ok(n) if n > 1 => Folds::KFold(n),
+                    _ => fail("--folds must be a number greater than 1"),
+                }
+            }
+            "--k" => {
+                k = match value().parse() {
+                    Ok(k) if k > 0 => k,
+                    _ => fail("--k must be a positive number"),
+                }
+            }
+            "--aggregation" => {
+                aggregation = match value().as_str() {
+                    "mean" => Aggregation::Mean,
+                    "min" => Aggregation::Min,
+                    "weighted" => Aggregation::DistanceWeighted,
+                    other => fail(&format!("unknown aggregation {}", other)),
+                }
+            }
+            "--json" => json = true,
+            "--confusion" => confusion = Some(value()),
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return;
+            }
+            _ if snapshot.is_none() && !arg.starts_with('-') => snapshot = Some(arg),
+            _ => fail(&format!("unexpected argument {}", arg)),
+        }
+    }
+
+    let path = snapshot.unwrap_or_else(|| fail("missing snapshot"));
+    let file = File::open(&path).unwrap_or_else(|e| fail(&format!("{}: {}", path, e)));
+    let samples: HashMap<String, Vec<StrokeSample>> = serde_json::from_reader(BufReader::new(file))
+        .unwrap_or_else(|e| fail(&format!("{}: {}", path, e)));
+    // the original detexify snapshots have base64 ids, symbols are looked up by base32 ids
+    let samples = to_base32_ids(samples).unwrap_or_else(|e| fail(&format!("{}: {}", path, e)));
+
+    let evaluation = cross_validate(&samples, folds, |c| {
+        c.with_k(k).with_aggregation(aggregation)
+    })
+    .unwrap_or_else(|e| fail(&e.to_string()));
+
+    if let Some(path) = confusion {
+        let export = if path.ends_with(".csv") {
+            evaluation.confusion.to_csv()
+        } else {
+            serde_json::to_string_pretty(&serde_json::json!({
+                "matrix": evaluation.confusion,
+                "most_confused": evaluation.confusion.most_confused(),
+            }))
+            .unwrap()
+        };
+        std::fs::write(&path, export).unwrap_or_else(|e| fail(&format!("{}: {}", path, e)));
+    }
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&evaluation).unwrap());
+        return;
+    }
+
+    for symbol in &evaluation.symbols {
+        println!(
+            "{}\tsamples {}\trecall {:.3}\tmean rank {:.2}",
+            symbol.id, symbol.samples, symbol.recall, symbol.mean_rank
+        );
+    }
+    println!();
+    println!("most confused:");
+    for pair in evaluation.confusion.most_confused().iter().take(10) {
+        println!("{}\t{}\t{}", pair.a_label, pair.b_label, pair.count);
+    }
+    println!();
+    println!(
+        "samples:   {} ({} skipped)",
+        evaluation.samples, evaluation.skipped
+    );
+    match (evaluation.top1, evaluation.top5, evaluation.mean_rank) {
+        (Some(top1), Some(top5), Some(mean_rank)) => {
+            println!("top-1:     {:.3}", top1);
+            println!("top-5:     {:.3}", top5);
+            println!("mean rank: {:.2}", mean_rank);
+        }
+        _ => println!("no sample had training samples of its symbol to be classified against"),
+    }
+}
@@ -1,4 +1,5 @@
 use crate::stroke_sample::StrokeSample;
+use crate::symbol::{base32_to_base64, base64_to_base32, to_base32_ids, IdEncoding};
 use itertools::Itertools;
 use serde::Serialize;
 use std::collections::HashMap;
@@ -13,26 +14,199 @@ struct Hit<T> {
     sample: T,
 }
 
+/// Temperature used until the classifier is calibrated
+pub const DEFAULT_TEMPERATURE: f64 = 1.0;
+
+/// Maximum number of samples kept per symbol when training
+pub const DEFAULT_SAMPLE_LIMIT: usize = 50;
+
 #[derive(Debug, Serialize)]
 pub struct Score {
     pub id: String,
     pub score: f64,
+    /// Probability that `id` is the drawn symbol, a softmax over the negated scores
+    pub confidence: f64,
+}
+
+/// How the distances to a symbol's `k` nearest samples are combined into its score
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Aggregation {
+    /// Mean distance
+    Mean,
+    /// Distance to the nearest sample, regardless of `k`
+    Min,
+    /// Mean weighted by inverse distance, so close samples dominate
+    DistanceWeighted,
+}
+
+impl Aggregation {
+    fn aggregate(self, nearest: &[f64]) -> f64 {
+        if nearest.is_empty() {
+            return f64::INFINITY;
+        }
+
+        match self {
+            Aggregation::Mean => nearest.iter().sum::<f64>() / nearest.len() as f64,
+            Aggregation::Min => nearest[0],
+            Aggregation::DistanceWeighted => {
+                if nearest[0] == 0.0 {
+                    0.0
+                } else {
+                    nearest.len() as f64 / nearest.iter().map(|d| 1.0 / d).sum::<f64>()
+                }
+            }
+        }
+    }
+}
+
+/// Result of classifying with rejection enabled, see [`Classifier::classify_with_rejection`]
+#[derive(Debug)]
+pub enum Classification {
+    /// The best match is within its symbol's threshold
+    Known(Vec<Score>),
+    /// The best match exceeds its symbol's threshold, the sample is probably not a symbol
+    Unknown(Vec<Score>),
 }
 
 pub struct Classifier {
     samples: HashMap<String, Vec<StrokeSample>>,
+    temperature: f64,
+    /// Tolerance the rejection thresholds are learned with, see [`Classifier::learn_thresholds`]
+    tolerance: Option<f64>,
+    /// Rejection thresholds of the symbols with more than one sample
+    thresholds: HashMap<String, f64>,
+    k: usize,
+    aggregation: Aggregation,
+    sample_limit: usize,
 }
 
 impl Classifier {
     pub fn new() -> Classifier {
+        Classifier::from_samples(HashMap::new())
+    }
+
+    pub(crate) fn from_samples(samples: HashMap<String, Vec<StrokeSample>>) -> Classifier {
         Classifier {
-            samples: HashMap::new(),
+            samples,
+            temperature: DEFAULT_TEMPERATURE,
+            tolerance: None,
+            thresholds: HashMap::new(),
+            k: 2,
+            aggregation: Aggregation::Mean,
+            sample_limit: DEFAULT_SAMPLE_LIMIT,
         }
     }
 
+    /// Sets the number of nearest samples of each symbol used to score it (default 2), symbols
+    /// with fewer samples are scored with all of them
+    pub fn with_k(mut self, k: usize) -> Self {
+        assert!(k > 0);
+        self.k = k;
+        self
+    }
+
+    /// Sets how the nearest samples are combined into a score (default [`Aggregation::Mean`])
+    pub fn with_aggregation(mut self, aggregation: Aggregation) -> Self {
+        self.aggregation = aggregation;
+        self
+    }
+
+    /// Reads a snapshot, converting its ids to base32 if they are base64 like those of the
+    /// original detexify snapshots
     pub fn from_snapshot<R: std::io::Read>(reader: R) -> serde_json::Result<Classifier> {
-        let samples = serde_json::from_reader(reader);
-        samples.map(|s| Classifier { samples: s })
+        let samples = serde_json::from_reader(reader)?;
+        let samples = to_base32_ids(samples).map_err(serde::de::Error::custom)?;
+        Ok(Classifier::from_samples(samples))
+    }
+
+    /// Writes the samples in the snapshot format read by [`Classifier::from_snapshot`]
+    pub fn to_snapshot<W: std::io::Write>(&self, writer: W) -> serde_json::Result<()> {
+        serde_json::to_writer(writer, &self.samples)
+    }
+
+    /// Writes the samples in the snapshot format with ids in `encoding`, base64 for the original
+    /// detexify backend
+    pub fn to_snapshot_with_encoding<W: std::io::Write>(
+        &self,
+        writer: W,
+        encoding: IdEncoding,
+    ) -> serde_json::Result<()> {
+        match encoding {
+            IdEncoding::Base32 => self.to_snapshot(writer),
+            IdEncoding::Base64 => {
+                let samples: HashMap<String, &Vec<StrokeSample>> = self
+                    .samples
+                    .iter()
+                    .map(|(id, samples)| {
+                        (base32_to_base64(id).unwrap_or_else(|| id.clone()), samples)
+                    })
+                    .collect();
+                serde_json::to_writer(writer, &samples)
+            }
+        }
+    }
+
+    /// Sets the maximum number of samples kept per symbol by [`Classifier::train`]
+    pub fn with_sample_limit(mut self, sample_limit: usize) -> Self {
+        assert!(sample_limit > 0);
+        self.sample_limit = sample_limit;
+        self
+    }
+
+    /// Adds `sample` to the samples of symbol `id`, dropping the oldest samples once the symbol
+    /// has more than the sample limit. Returns the number of samples the symbol now has.
+    ///
+    /// The symbol's rejection threshold is relearned if thresholds were learned.
+    pub fn train(&mut self, id: String, sample: StrokeSample) -> usize {
+        let samples = self.samples.entry(id.clone()).or_default();
+        samples.insert(0, sample);
+        samples.truncate(self.sample_limit);
+        let count = samples.len();
+
+        self.update_threshold(&id);
+        count
+    }
+
+    /// Softmax temperature used to turn scores into confidences
+    pub fn temperature(&self) -> f64 {
+        self.temperature
+    }
+
+    /// Sets the softmax temperature (default [`DEFAULT_TEMPERATURE`]), e.g. one found by
+    /// [`Classifier::calibrate`] on a previous run
+    pub fn set_temperature(&mut self, temperature: f64) {
+        assert!(temperature > 0.0);
+        self.temperature = temperature;
+    }
+
+    /// Number of symbols with at least one sample
+    pub fn symbol_count(&self) -> usize {
+        self.samples.values().filter(|s| !s.is_empty()).count()
+    }
+
+    /// Total number of samples over all symbols
+    pub fn sample_count(&self) -> usize {
+        self.samples.values().map(Vec::len).sum()
+    }
+
+    /// Ids of the symbols with at least one sample and their number of samples
+    pub fn sample_counts(&self) -> impl Iterator<Item = (&str, usize)> {
+        self.samples
+            .iter()
+            .filter(|(_, s)| !s.is_empty())
+            .map(|(id, s)| (id.as_str(), s.len()))
+    }
+
+    /// Adds the samples of `other` after this classifier's own, keeping at most the sample
+    /// limit per symbol. Rejection thresholds of the merged symbols are relearned if thresholds
+    /// were learned, those of `other` are ignored.
+    pub fn merge(&mut self, other: Classifier) {
+        for (id, samples) in other.samples {
+            let own = self.samples.entry(id.clone()).or_default();
+            own.extend(samples);
+            own.truncate(self.sample_limit);
+            self.update_threshold(&id);
+        }
     }
 
     pub fn classify(&self, unknown: StrokeSample) -> Option<Vec<Score>> {
@@ -40,29 +214,223 @@ impl Classifier {
             return None;
         }
 
+        self.scores(self.distances(&unknown))
+    }
+
+    /// Classifies `unknown` as if the samples of `base` were added to this classifier's, using
+    /// this classifier's settings. Lets a small classifier of extra samples share a large base
+    /// without copying it.
+    pub fn classify_with_base(
+        &self,
+        base: &Classifier,
+        unknown: StrokeSample,
+    ) -> Option<Vec<Score>> {
+        if unknown.is_empty() {
+            return None;
+        }
+
+        let own = self.samples.iter().map(|(id, samples)| {
+            let base_samples = base.samples.get(id).into_iter().flatten();
+            (
+                id,
+                self.symbol_distance(&unknown, samples.iter().chain(base_samples)),
+            )
+        });
+        let base_only = base
+            .samples
+            .iter()
+            .filter(|(id, _)| !self.samples.contains_key(*id))
+            .map(|(id, samples)| (id, self.symbol_distance(&unknown, samples.iter())));
+
+        self.scores(own.chain(base_only).collect())
+    }
+
+    fn scores(&self, distances: Vec<(&String, f64)>) -> Option<Vec<Score>> {
+        let confidences = softmax(&distances, self.temperature);
+
         Some(
-            self.samples
-                .iter()
-                .map(|(id, samples)| {
-                    let mean_dist = samples
-                        .iter()
-                        .cloned()
-                        .map(|s| StrokeSample::distance(unknown.clone(), s))
-                        .sorted_by(|x, y| x.partial_cmp(y).unwrap())
-                        .take(2)
-                        .fold(0.0, |acc, x| acc + x)
-                        / 2.0;
-
-                    (id, mean_dist)
-                })
-                .map(|(id, dist)| Score {
+            distances
+                .into_iter()
+                .zip(confidences)
+                .map(|((id, dist), confidence)| Score {
                     id: id.clone(),
                     score: dist,
+                    confidence,
                 })
                 .sorted_by(|x, y| x.score.partial_cmp(&y.score).unwrap())
                 .collect(),
         )
     }
+
+    /// Classifies `unknown`, rejecting it when the best score exceeds the threshold learned by
+    /// [`Classifier::learn_thresholds`]. Without learned thresholds nothing is rejected.
+    pub fn classify_with_rejection(&self, unknown: StrokeSample) -> Option<Classification> {
+        let scores = self.classify(unknown)?;
+
+        let rejected = scores.first().is_some_and(|best| self.rejects(best));
+
+        Some(if rejected {
+            Classification::Unknown(scores)
+        } else {
+            Classification::Known(scores)
+        })
+    }
+
+    /// Whether `best`, the best score of a classification, exceeds the rejection threshold of
+    /// its symbol. Nothing is rejected without learned thresholds.
+    pub fn rejects(&self, best: &Score) -> bool {
+        matches!(self.threshold(&best.id), Some(threshold) if best.score > threshold)
+    }
+
+    /// Rejection threshold of symbol `id`. Symbols with a single sample have no intra-class
+    /// distances and use the mean threshold of the other symbols.
+    pub fn threshold(&self, id: &str) -> Option<f64> {
+        if let Some(&threshold) = self.thresholds.get(id) {
+            return Some(threshold);
+        }
+        if self.thresholds.is_empty() {
+            return None;
+        }
+
+        Some(self.thresholds.values().sum::<f64>() / self.thresholds.len() as f64)
+    }
+
+    /// Tolerance the rejection thresholds were learned with, `None` if they weren't
+    pub fn rejection_tolerance(&self) -> Option<f64> {
+        self.tolerance
+    }
+
+    /// Learns a rejection threshold for every symbol from the intra-class distances of its
+    /// samples: each sample is scored against the remaining samples of its symbol and the
+    /// threshold is the mean of those scores plus `tolerance` standard deviations.
+    ///
+    /// Thresholds aren't part of the snapshot format, they are kept up to date by
+    /// [`Classifier::train`] and [`Classifier::merge`] and have to be learned again after
+    /// loading a snapshot.
+    pub fn learn_thresholds(&mut self, tolerance: f64) {
+        self.tolerance = Some(tolerance);
+        self.thresholds = self
+            .samples
+            .keys()
+            .filter_map(|id| Some((id.clone(), self.learn_threshold(id)?)))
+            .collect();
+    }
+
+    /// Relearns the threshold of symbol `id` after its samples changed
+    fn update_threshold(&mut self, id: &str) {
+        match self.learn_threshold(id) {
+            Some(threshold) => {
+                self.thresholds.insert(id.to_string(), threshold);
+            }
+            None => {
+                self.thresholds.remove(id);
+            }
+        }
+    }
+
+    /// `None` if no thresholds were learned or the symbol has fewer than two samples
+    fn learn_threshold(&self, id: &str) -> Option<f64> {
+        let tolerance = self.tolerance?;
+        let samples = self.samples.get(id).filter(|samples| samples.len() > 1)?;
+
+        let scores: Vec<f64> = (0..samples.len())
+            .map(|i| {
+                let others = samples
+                    .iter()
+                    .enumerate()
+                    .filter(|&(j, _)| j != i)
+                    .map(|(_, s)| s);
+                self.symbol_distance(&samples[i], others)
+            })
+            .collect();
+
+        let n = scores.len() as f64;
+        let mean = scores.iter().sum::<f64>() / n;
+        let variance = scores.iter().map(|s| (s - mean).powi(2)).sum::<f64>() / n;
+
+        Some(mean + tolerance * variance.sqrt())
+    }
+
+    /// Fits the softmax temperature to a labelled dataset (in the snapshot format) by minimising
+    /// the negative log likelihood of the true labels, returning the new temperature.
+    ///
+    /// The dataset should be held out from the classifier's own samples. Labels the classifier
+    /// has no samples for are ignored, if nothing is left the temperature is unchanged.
+    pub fn calibrate(&mut self, labelled: &HashMap<String, Vec<StrokeSample>>) -> f64 {
+        let observations: Vec<(Vec<(&String, f64)>, usize)> = labelled
+            .iter()
+            .flat_map(|(label, samples)| samples.iter().map(move |s| (label, s)))
+            .filter(|(_, sample)| !sample.is_empty())
+            .filter_map(|(label, sample)| {
+                let distances = self.distances(sample);
+                let truth = distances.iter().position(|(id, _)| *id == label)?;
+                Some((distances, truth))
+            })
+            .collect();
+
+        if observations.is_empty() {
+            return self.temperature;
+        }
+
+        let nll = |log_t: f64| {
+            observations
+                .iter()
+                .map(|(distances, truth)| -softmax(distances, log_t.exp())[*truth].ln())
+                .sum::<f64>()
+        };
+
+        // golden section search over log(temperature)
+        let ratio = (5f64.sqrt() - 1.0) / 2.0;
+        let (mut lo, mut hi) = ((1e-4f64).ln(), (1e2f64).ln());
+        while hi - lo > 1e-3 {
+            let a = hi - ratio * (hi - lo);
+            let b = lo + ratio * (hi - lo);
+            if nll(a) < nll(b) {
+                hi = b;
+            } else {
+                lo = a;
+            }
+        }
+
+        self.temperature = ((lo + hi) / 2.0).exp();
+        self.temperature
+    }
+
+    fn distances(&self, unknown: &StrokeSample) -> Vec<(&String, f64)> {
+        self.samples
+            .iter()
+            .map(|(id, samples)| (id, self.symbol_distance(unknown, samples.iter())))
+            .collect()
+    }
+
+    fn symbol_distance<'a>(
+        &self,
+        unknown: &StrokeSample,
+        samples: impl Iterator<Item = &'a StrokeSample>,
+    ) -> f64 {
+        let nearest: Vec<f64> = samples
+            .cloned()
+            .map(|s| StrokeSample::distance(unknown.clone(), s))
+            .sorted_by(|x, y| x.partial_cmp(y).unwrap())
+            .take(self.k)
+            .collect();
+
+        self.aggregation.aggregate(&nearest)
+    }
+}
+
+fn softmax<T>(distances: &[(T, f64)], temperature: f64) -> Vec<f64> {
+    let min = distances
+        .iter()
+        .map(|(_, d)| *d)
+        .fold(f64::INFINITY, f64::min);
+    let weights: Vec<f64> = distances
+        .iter()
+        .map(|(_, d)| (-(d - min) / temperature).exp())
+        .collect();
+    let total: f64 = weights.iter().sum();
+
+    weights.into_iter().map(|w| w / total).collect()
 }
 
 impl Default for Classifier {
@@ -73,43 +441,248 @@ impl Default for Classifier {
             ))
             .unwrap()
             .into_iter()
-            .map(|(id_base64, strokes)| {
-                let id = base64::decode(id_base64).unwrap();
-                let id_base32 = base32::encode(base32::Alphabet::RFC4648 { padding: false }, &id);
-
-                return (id_base32, strokes);
-            })
+            .map(|(id_base64, strokes)| (base64_to_base32(&id_base64).unwrap(), strokes))
             .collect();
 
-        Classifier { samples }
+        Classifier::from_samples(samples)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::stroke::Stroke;
+    use crate::test_support::line;
+    use crate::{point::Point, stroke::Stroke};
 
     #[test]
     fn default_classifier() {
         Classifier::default();
     }
-}
 
-// fn insert_with_limit<T: Sample>(
-//     limit: i64,
-//     identifier: String,
-//     sample: T,
-//     map: HashMap<String, Vec>,
-// ) -> HashMap<String, Vec> {
-//     todo!()
-// }
-
-// fn train_classifier<T: Sample>(classifier: Classifier, identifier: String, sample: T) {
-//     insert_with_limit(
-//         classifier.sample_limit,
-//         identifier,
-//         sample,
-//         classifier.samples,
-//     );
-// }
+    #[test]
+    fn test_confidence() {
+        let horizontal = line(Point { x: 0.0, y: 0.0 }, Point { x: 1.0, y: 0.0 });
+        let vertical = line(Point { x: 0.0, y: 0.0 }, Point { x: 0.0, y: 1.0 });
+
+        let mut samples = HashMap::new();
+        samples.insert("horizontal".to_string(), vec![horizontal.clone()]);
+        samples.insert("vertical".to_string(), vec![vertical.clone()]);
+        let mut classifier = Classifier::from_samples(samples);
+
+        let scores = classifier.classify(horizontal.clone()).unwrap();
+        assert_eq!(scores[0].id, "horizontal");
+        assert!(scores[0].confidence > scores[1].confidence);
+        assert!((scores.iter().map(|s| s.confidence).sum::<f64>() - 1.0).abs() < 1e-9);
+
+        let mut labelled = HashMap::new();
+        labelled.insert("horizontal".to_string(), vec![horizontal.clone()]);
+        labelled.insert("vertical".to_string(), vec![vertical]);
+        let temperature = classifier.calibrate(&labelled);
+        assert!(temperature < DEFAULT_TEMPERATURE);
+
+        let scores = classifier.classify(horizontal.clone()).unwrap();
+        assert!(scores[0].confidence > 0.99);
+
+        classifier.set_temperature(DEFAULT_TEMPERATURE);
+        let scores = classifier.classify(horizontal).unwrap();
+        assert!(scores[0].confidence < 0.99);
+    }
+
+    #[test]
+    fn test_rejection() {
+        let horizontal = |y| {
+            line(
+                Point { x: 0.0, y },
+                Point {
+                    x: 1.0,
+                    y: y + 0.05,
+                },
+            )
+        };
+
+        let mut samples = HashMap::new();
+        samples.insert(
+            "horizontal".to_string(),
+            vec![horizontal(0.0), horizontal(0.1), horizontal(0.2)],
+        );
+        let mut classifier = Classifier::from_samples(samples);
+
+        let scribble = StrokeSample::new(vec![Stroke::new(vec![
+            Point { x: 0.0, y: 0.0 },
+            Point { x: 1.0, y: 1.0 },
+            Point { x: 0.0, y: 1.0 },
+            Point { x: 1.0, y: 0.0 },
+        ])])
+        .unwrap();
+
+        match classifier.classify_with_rejection(scribble.clone()) {
+            Some(Classification::Known(_)) => {}
+            other => panic!("expected known without thresholds, got {:?}", other),
+        }
+
+        classifier.learn_thresholds(2.0);
+
+        match classifier.classify_with_rejection(scribble) {
+            Some(Classification::Unknown(_)) => {}
+            other => panic!("expected unknown, got {:?}", other),
+        }
+        match classifier.classify_with_rejection(horizontal(0.15)) {
+            Some(Classification::Known(scores)) => assert_eq!(scores[0].id, "horizontal"),
+            other => panic!("expected known, got {:?}", other),
+        }
+
+        // symbols trained after learning get thresholds, a single sample uses the mean
+        let vertical = |x| {
+            line(
+                Point { x, y: 0.0 },
+                Point {
+                    x: x + 0.05,
+                    y: 1.0,
+                },
+            )
+        };
+        classifier.train("vertical".to_string(), vertical(0.0));
+        assert!(!classifier.thresholds.contains_key("vertical"));
+        assert_eq!(
+            classifier.threshold("vertical"),
+            classifier.threshold("horizontal")
+        );
+        classifier.train("vertical".to_string(), vertical(0.1));
+        assert!(classifier.thresholds.contains_key("vertical"));
+
+        // thresholds aren't in snapshots
+        let mut snapshot = Vec::new();
+        classifier.to_snapshot(&mut snapshot).unwrap();
+        let restored = Classifier::from_snapshot(snapshot.as_slice()).unwrap();
+        assert_eq!(restored.threshold("horizontal"), None);
+        assert_eq!(restored.rejection_tolerance(), None);
+    }
+
+    #[test]
+    fn test_aggregation() {
+        assert_eq!(Aggregation::Mean.aggregate(&[1.0, 2.0, 3.0]), 2.0);
+        assert_eq!(Aggregation::Mean.aggregate(&[1.0]), 1.0);
+        assert_eq!(Aggregation::Min.aggregate(&[1.0, 2.0]), 1.0);
+        assert_eq!(Aggregation::DistanceWeighted.aggregate(&[1.0, 3.0]), 1.5);
+        assert_eq!(Aggregation::DistanceWeighted.aggregate(&[0.0, 3.0]), 0.0);
+        assert_eq!(Aggregation::Mean.aggregate(&[]), f64::INFINITY);
+    }
+
+    #[test]
+    fn test_fewer_samples_than_k() {
+        let sample = line(Point { x: 0.0, y: 0.0 }, Point { x: 1.0, y: 0.0 });
+
+        let mut samples = HashMap::new();
+        samples.insert("horizontal".to_string(), vec![sample.clone()]);
+        let classifier = Classifier::from_samples(samples).with_k(5);
+
+        let scores = classifier.classify(sample).unwrap();
+        assert_eq!(scores[0].score, 0.0);
+    }
+
+    #[test]
+    fn test_snapshot_round_trip() {
+        let mut classifier = Classifier::new();
+        classifier.train(
+            "horizontal".to_string(),
+            line(Point { x: 0.0, y: 0.0 }, Point { x: 1.0, y: 0.0 }),
+        );
+
+        let mut snapshot = Vec::new();
+        classifier.to_snapshot(&mut snapshot).unwrap();
+        let restored = Classifier::from_snapshot(snapshot.as_slice()).unwrap();
+
+        assert_eq!(restored.samples.len(), 1);
+        assert_eq!(restored.samples["horizontal"].len(), 1);
+
+        // base64 ids of the original detexify snapshots are converted
+        let id = "NRQXIZLYGJSS2T2UGEWV65DFPB2GC43DNFUWG2LSMN2W2";
+        let mut classifier = Classifier::new();
+        classifier.train(
+            crate::symbol::base32_to_base64(id).unwrap(),
+            line(Point { x: 0.0, y: 0.0 }, Point { x: 1.0, y: 0.0 }),
+        );
+        let mut snapshot = Vec::new();
+        classifier.to_snapshot(&mut snapshot).unwrap();
+        let restored = Classifier::from_snapshot(snapshot.as_slice()).unwrap();
+        assert_eq!(restored.samples[id].len(), 1);
+
+        let mut snapshot = Vec::new();
+        restored
+            .to_snapshot_with_encoding(&mut snapshot, IdEncoding::Base64)
+            .unwrap();
+        let snapshot: HashMap<String, serde_json::Value> =
+            serde_json::from_slice(&snapshot).unwrap();
+        assert!(snapshot.contains_key(&base32_to_base64(id).unwrap()));
+    }
+
+    #[test]
+    fn test_train() {
+        let horizontal = line(Point { x: 0.0, y: 0.0 }, Point { x: 1.0, y: 0.0 });
+        let vertical = line(Point { x: 0.0, y: 0.0 }, Point { x: 0.0, y: 1.0 });
+
+        let mut classifier = Classifier::new().with_sample_limit(2);
+        assert_eq!(
+            classifier.train("horizontal".to_string(), horizontal.clone()),
+            1
+        );
+        assert_eq!(
+            classifier.train("horizontal".to_string(), horizontal.clone()),
+            2
+        );
+        assert_eq!(
+            classifier.train("horizontal".to_string(), horizontal.clone()),
+            2
+        );
+        classifier.train("vertical".to_string(), vertical);
+        assert_eq!(classifier.symbol_count(), 2);
+        assert_eq!(classifier.sample_count(), 3);
+
+        let scores = classifier.classify(horizontal).unwrap();
+        assert_eq!(scores[0].id, "horizontal");
+    }
+
+    #[test]
+    fn test_merge() {
+        let horizontal = line(Point { x: 0.0, y: 0.0 }, Point { x: 1.0, y: 0.0 });
+        let vertical = line(Point { x: 0.0, y: 0.0 }, Point { x: 0.0, y: 1.0 });
+
+        let mut classifier = Classifier::new().with_sample_limit(2);
+        classifier.train("horizontal".to_string(), horizontal.clone());
+
+        let mut other = Classifier::new();
+        other.train("horizontal".to_string(), horizontal.clone());
+        other.train("horizontal".to_string(), horizontal);
+        other.train("vertical".to_string(), vertical);
+        classifier.merge(other);
+
+        let mut counts: Vec<(&str, usize)> = classifier.sample_counts().collect();
+        counts.sort();
+        assert_eq!(counts, vec![("horizontal", 2), ("vertical", 1)]);
+    }
+
+    #[test]
+    fn test_classify_with_base() {
+        let horizontal = line(Point { x: 0.0, y: 0.0 }, Point { x: 1.0, y: 0.0 });
+        let vertical = line(Point { x: 0.0, y: 0.0 }, Point { x: 0.0, y: 1.0 });
+        let diagonal = line(Point { x: 0.0, y: 0.0 }, Point { x: 1.0, y: 1.0 });
+
+        let mut base = Classifier::new();
+        base.train("horizontal".to_string(), horizontal.clone());
+        base.train("vertical".to_string(), vertical.clone());
+
+        let mut overlay = Classifier::new().with_aggregation(Aggregation::Min);
+        overlay.train("diagonal".to_string(), diagonal.clone());
+        overlay.train("vertical".to_string(), horizontal.clone());
+
+        let scores = overlay.classify_with_base(&base, diagonal).unwrap();
+        assert_eq!(scores.len(), 3);
+        assert_eq!(scores[0].id, "diagonal");
+
+        // the overlay's sample of "vertical" matches as well as the base's "horizontal" sample
+        let scores = overlay.classify_with_base(&base, horizontal).unwrap();
+        let vertical_score = scores.iter().find(|s| s.id == "vertical").unwrap().score;
+        assert_eq!(vertical_score, scores[0].score);
+        assert_eq!(overlay.sample_count(), 2);
+    }
+}
@@ -0,0 +1,305 @@
+use crate::{classifier::Classifier, stroke_sample::StrokeSample, symbol::Symbol};
+use serde::Serialize;
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+use std::fmt::{self, Write};
+
+/// How labelled samples are split into training and test sets
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Folds {
+    /// Every sample is classified by a classifier trained on all the other samples
+    LeaveOneOut,
+    /// Samples are split into `n` folds, each classified by a classifier trained on the rest
+    KFold(usize),
+}
+
+/// Returned by `cross_validate` for a `Folds::KFold` with fewer than 2 folds
+#[derive(Debug, PartialEq)]
+pub struct TooFewFolds(pub usize);
+
+impl fmt::Display for TooFewFolds {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "cannot cross-validate with {} folds, at least 2 are needed",
+            self.0
+        )
+    }
+}
+
+impl Error for TooFewFolds {}
+
+#[derive(Debug, Serialize)]
+pub struct SymbolReport {
+    pub id: String,
+    /// Number of samples of this symbol that were classified
+    pub samples: usize,
+    /// Fraction of samples ranked first
+    pub recall: f64,
+    pub mean_rank: f64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Evaluation {
+    /// Number of samples that were classified
+    pub samples: usize,
+    /// Number of samples skipped because their symbol had no training samples in their fold
+    pub skipped: usize,
+    /// Unset, as the metrics below, when no sample was classified
+    pub top1: Option<f64>,
+    pub top5: Option<f64>,
+    pub mean_rank: Option<f64>,
+    pub symbols: Vec<SymbolReport>,
+    pub confusion: ConfusionMatrix,
+}
+
+/// Sparse confusion matrix of top-1 predictions, keyed by actual then predicted symbol id
+#[derive(Debug, Default, Serialize)]
+pub struct ConfusionMatrix(BTreeMap<String, BTreeMap<String, usize>>);
+
+/// Two symbols mistaken for each other, counted in both directions
+#[derive(Debug, PartialEq, Serialize)]
+pub struct ConfusedPair {
+    pub a: String,
+    pub a_label: String,
+    pub b: String,
+    pub b_label: String,
+    pub count: usize,
+}
+
+impl ConfusionMatrix {
+    pub fn add(&mut self, actual: &str, predicted: &str) {
+        *self
+            .0
+            .entry(actual.to_string())
+            .or_default()
+            .entry(predicted.to_string())
+            .or_default() += 1;
+    }
+
+    /// Number of samples of `actual` predicted as `predicted`
+    pub fn get(&self, actual: &str, predicted: &str) -> usize {
+        self.0
+            .get(actual)
+            .and_then(|row| row.get(predicted))
+            .cloned()
+            .unwrap_or(0)
+    }
+
+    /// Pairs of distinct symbols ordered from most to least confused
+    pub fn most_confused(&self) -> Vec<ConfusedPair> {
+        let mut pairs: BTreeMap<(&String, &String), usize> = BTreeMap::new();
+        for (actual, row) in &self.0 {
+            for (predicted, count) in row.iter().filter(|(p, _)| *p != actual) {
+                let key = if actual < predicted {
+                    (actual, predicted)
+                } else {
+                    (predicted, actual)
+                };
+                *pairs.entry(key).or_default() += count;
+            }
+        }
+
+        let mut pairs: Vec<ConfusedPair> = pairs
+            .into_iter()
+            .map(|((a, b), count)| ConfusedPair {
+                a: a.clone(),
+                a_label: label(a),
+                b: b.clone(),
+                b_label: label(b),
+                count,
+            })
+            .collect();
+        pairs.sort_by_key(|p| Reverse(p.count));
+        pairs
+    }
+
+    /// Exports the non-zero entries as CSV with readable labels
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("actual_id,actual,predicted_id,predicted,count\n");
+        for (actual, row) in &self.0 {
+            for (predicted, count) in row {
+                writeln!(
+                    csv,
+                    "{},{},{},{},{}",
+                    quote(actual),
+                    quote(&label(actual)),
+                    quote(predicted),
+                    quote(&label(predicted)),
+                    count
+                )
+                .unwrap();
+            }
+        }
+        csv
+    }
+}
+
+/// Readable label for a symbol id, the command if the id is in the symbol table
+fn label(id: &str) -> String {
+    Symbol::from_id(id)
+        .map(|symbol| symbol.command.to_string())
+        .unwrap_or_else(|| id.to_string())
+}
+
+fn quote(field: &str) -> String {
+    format!("\"{}\"", field.replace('"', "\"\""))
+}
+
+/// Cross-validates classifiers built from `samples` (in the snapshot format), `configure` is
+/// applied to every classifier before it is used so settings like `k` can be compared.
+pub fn cross_validate<F>(
+    samples: &HashMap<String, Vec<StrokeSample>>,
+    folds: Folds,
+    configure: F,
+) -> Result<Evaluation, TooFewFolds>
+where
+    F: Fn(Classifier) -> Classifier,
+{
+    // sort for a deterministic fold assignment
+    let labelled: Vec<(&String, &StrokeSample)> = samples
+        .iter()
+        .collect::<BTreeMap<_, _>>()
+        .into_iter()
+        .flat_map(|(id, samples)| samples.iter().map(move |s| (id, s)))
+        .collect();
+
+    let n = match folds {
+        Folds::LeaveOneOut => labelled.len(),
+        Folds::KFold(n) if n < 2 => return Err(TooFewFolds(n)),
+        Folds::KFold(n) => n,
+    };
+
+    let mut ranks: BTreeMap<&String, Vec<usize>> = BTreeMap::new();
+    let mut confusion = ConfusionMatrix::default();
+    let mut skipped = 0;
+
+    for fold in 0..n {
+        let mut training: HashMap<String, Vec<StrokeSample>> = HashMap::new();
+        for (i, &(id, sample)) in labelled.iter().enumerate() {
+            if i % n != fold {
+                training.entry(id.clone()).or_default().push(sample.clone());
+            }
+        }
+
+        let classifier = configure(Classifier::from_samples(training));
+
+        for &(id, sample) in labelled.iter().skip(fold).step_by(n) {
+            let scores = classifier.classify(sample.clone()).unwrap_or_default();
+
+            match scores.iter().position(|s| &s.id == id) {
+                Some(rank) => {
+                    ranks.entry(id).or_default().push(rank + 1);
+                    confusion.add(id, &scores[0].id);
+                }
+                None => skipped += 1,
+            }
+        }
+    }
+
+    let all: Vec<usize> = ranks.values().flatten().cloned().collect();
+    let fraction = |ranks: &[usize], top: usize| {
+        ranks.iter().filter(|&&r| r <= top).count() as f64 / ranks.len() as f64
+    };
+    let mean = |ranks: &[usize]| ranks.iter().sum::<usize>() as f64 / ranks.len() as f64;
+    let evaluated = !all.is_empty();
+
+    Ok(Evaluation {
+        samples: all.len(),
+        skipped,
+        top1: Some(fraction(&all, 1)).filter(|_| evaluated),
+        top5: Some(fraction(&all, 5)).filter(|_| evaluated),
+        mean_rank: Some(mean(&all)).filter(|_| evaluated),
+        symbols: ranks
+            .iter()
+            .map(|(id, ranks)| SymbolReport {
+                id: id.to_string(),
+                samples: ranks.len(),
+                recall: fraction(ranks, 1),
+                mean_rank: mean(ranks),
+            })
+            .collect(),
+        confusion,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::point::Point;
+    use crate::test_support::line;
+
+    #[test]
+    fn test_cross_validate() {
+        let horizontal = line(Point { x: 0.0, y: 0.0 }, Point { x: 1.0, y: 0.0 });
+        let vertical = line(Point { x: 0.0, y: 0.0 }, Point { x: 0.0, y: 1.0 });
+        let lonely = line(Point { x: 0.0, y: 0.0 }, Point { x: 1.0, y: 1.0 });
+
+        let mut samples = HashMap::new();
+        samples.insert("horizontal".to_string(), vec![horizontal.clone(); 3]);
+        samples.insert("vertical".to_string(), vec![vertical.clone(); 3]);
+        samples.insert("lonely".to_string(), vec![lonely]);
+
+        let evaluation = cross_validate(&samples, Folds::LeaveOneOut, |c| c).unwrap();
+        assert_eq!(evaluation.samples, 6);
+        assert_eq!(evaluation.skipped, 1);
+        assert_eq!(evaluation.top1, Some(1.0));
+        assert_eq!(evaluation.mean_rank, Some(1.0));
+        assert_eq!(evaluation.symbols.len(), 2);
+
+        let evaluation = cross_validate(&samples, Folds::KFold(3), |c| c.with_k(1)).unwrap();
+        assert_eq!(evaluation.top5, Some(1.0));
+
+        for n in 0..2 {
+            assert_eq!(
+                cross_validate(&samples, Folds::KFold(n), |c| c).unwrap_err(),
+                TooFewFolds(n)
+            );
+        }
+    }
+
+    #[test]
+    fn test_cross_validate_nothing_evaluated() {
+        let evaluation = cross_validate(&HashMap::new(), Folds::KFold(2), |c| c).unwrap();
+        assert_eq!(evaluation.samples, 0);
+        assert_eq!(evaluation.top1, None);
+
+        let mut samples = HashMap::new();
+        samples.insert(
+            "lonely".to_string(),
+            vec![line(Point { x: 0.0, y: 0.0 }, Point { x: 1.0, y: 1.0 })],
+        );
+        let evaluation = cross_validate(&samples, Folds::LeaveOneOut, |c| c).unwrap();
+        assert_eq!(evaluation.samples, 0);
+        assert_eq!(evaluation.skipped, 1);
+        assert_eq!(evaluation.mean_rank, None);
+        assert!(serde_json::to_string(&evaluation)
+            .unwrap()
+            .contains("\"top1\":null"));
+    }
+
+    #[test]
+    fn test_confusion_matrix() {
+        let textless = "NRQXIZLYGJSS2T2UGEWV65DFPB2GYZLTOM";
+        let mut confusion = ConfusionMatrix::default();
+        confusion.add("a", "a");
+        confusion.add("a", "b");
+        confusion.add("b", "a");
+        confusion.add("b", textless);
+
+        assert_eq!(confusion.get("a", "b"), 1);
+        assert_eq!(confusion.get("b", "b"), 0);
+
+        let pairs = confusion.most_confused();
+        assert_eq!(pairs.len(), 2);
+        assert_eq!((pairs[0].a.as_str(), pairs[0].b.as_str()), ("a", "b"));
+        assert_eq!(pairs[0].count, 2);
+        assert_eq!(pairs[1].a_label, "\\textless");
+
+        assert_eq!(
+            confusion.to_csv().lines().nth(3),
+            Some(format!("\"b\",\"b\",\"{}\",\"\\textless\",1", textless).as_str())
+        );
+    }
+}
@@ -3,8 +3,12 @@ pub mod rect;
 pub mod stroke;
 pub mod classifier;
 pub mod dtw;
+pub mod evaluation;
 pub mod stroke_sample;
 pub mod symbol;
+pub mod svg;
+#[cfg(test)]
+mod test_support;
 
 pub use self::point::*;
 pub use self::stroke::*;
@@ -7,31 +7,117 @@ use crate::{
 };
 use serde::{Deserialize, Serialize};
 use std::f64::consts::PI;
+use std::fmt;
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct StrokeSample {
     strokes: Vec<Stroke>,
 }
 
+/// A step of the preprocessing done by [`StrokeSample::new`], applied to every stroke
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Stage {
+    /// The non-empty strokes of the input, at most 10
+    Input,
+    /// Consecutive duplicate points removed
+    Dedup,
+    /// Points averaged with their neighbours
+    Smooth,
+    /// Fit into the unit square keeping the aspect ratio
+    AspectRefit,
+    /// Points spaced evenly along the stroke
+    Redistribute,
+    /// Duplicate points left by redistributing removed
+    DedupRedistributed,
+    /// Only the points where the stroke changes direction kept
+    Dominant,
+}
+
+impl Stage {
+    /// Every stage in the order they are applied
+    pub const ALL: [Stage; 7] = [
+        Stage::Input,
+        Stage::Dedup,
+        Stage::Smooth,
+        Stage::AspectRefit,
+        Stage::Redistribute,
+        Stage::DedupRedistributed,
+        Stage::Dominant,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Stage::Input => "input",
+            Stage::Dedup => "dedup",
+            Stage::Smooth => "smooth",
+            Stage::AspectRefit => "aspect refit",
+            Stage::Redistribute => "redistribute",
+            Stage::DedupRedistributed => "dedup redistributed",
+            Stage::Dominant => "dominant",
+        }
+    }
+
+    fn apply(self, stroke: &mut Stroke) {
+        match self {
+            Stage::Input => {}
+            Stage::Dedup | Stage::DedupRedistributed => stroke.dedup(),
+            Stage::Smooth => stroke.smooth(),
+            Stage::AspectRefit => stroke.aspect_refit(Rect::new(ZERO_POINT, ONE_POINT)),
+            Stage::Redistribute => stroke.redistribute(10),
+            Stage::Dominant => stroke.dominant(2.0 * PI * 15.0 / 360.0),
+        }
+    }
+}
+
+impl fmt::Display for Stage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
 impl StrokeSample {
-    pub fn new(mut strokes: Vec<Stroke>) -> Option<Self> {
-        strokes = strokes.into_iter().filter(|s| !s.is_empty()).collect();
+    pub fn new(strokes: Vec<Stroke>) -> Option<Self> {
+        let mut strokes = StrokeSample::input(strokes)?;
+        for stroke in strokes.iter_mut() {
+            for stage in Stage::ALL.iter() {
+                stage.apply(stroke);
+            }
+        }
+
+        Some(StrokeSample { strokes })
+    }
+
+    /// Preprocesses `strokes` like [`StrokeSample::new`], returning the strokes after every
+    /// stage. `None` if the strokes have no points.
+    pub fn stages(strokes: Vec<Stroke>) -> Option<Vec<(Stage, Vec<Stroke>)>> {
+        let mut strokes = StrokeSample::input(strokes)?;
+        let stages = Stage::ALL
+            .iter()
+            .map(|&stage| {
+                for stroke in strokes.iter_mut() {
+                    stage.apply(stroke);
+                }
+                (stage, strokes.clone())
+            })
+            .collect();
+
+        Some(stages)
+    }
+
+    fn input(strokes: Vec<Stroke>) -> Option<Vec<Stroke>> {
+        let mut strokes: Vec<Stroke> = strokes.into_iter().filter(|s| !s.is_empty()).collect();
 
         if strokes.is_empty() {
             return None;
         }
 
         strokes.truncate(10);
-        for stroke in strokes.iter_mut() {
-            stroke.dedup();
-            stroke.smooth();
-            stroke.aspect_refit(Rect::new(ZERO_POINT, ONE_POINT));
-            stroke.redistribute(10);
-            stroke.dedup();
-            stroke.dominant(2.0 * PI * 15.0 / 360.0)
-        }
+        Some(strokes)
+    }
 
-        Some(StrokeSample { strokes })
+    /// The preprocessed strokes
+    pub fn strokes(&self) -> &[Stroke] {
+        &self.strokes
     }
 
     pub(crate) fn is_empty(&self) -> bool {
@@ -51,7 +137,7 @@ impl Sample<StrokeSample> for StrokeSample {
 
 #[cfg(test)]
 mod tests {
-    use crate::{point::Point, Stroke, StrokeSample};
+    use crate::{point::Point, Stage, Stroke, StrokeSample};
 
     #[test]
     fn test_sample() {
@@ -113,6 +199,33 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_stages() {
+        let strokes = vec![
+            Stroke::new(vec![
+                Point { x: 0.0, y: 0.0 },
+                Point { x: 0.0, y: 0.0 },
+                Point { x: 40.0, y: 50.0 },
+                Point { x: 100.0, y: 0.0 },
+            ]),
+            Stroke::new(vec![]),
+        ];
+
+        let stages = StrokeSample::stages(strokes.clone()).unwrap();
+        let names: Vec<Stage> = stages.iter().map(|(stage, _)| *stage).collect();
+        assert_eq!(names, Stage::ALL);
+
+        assert_eq!(stages[0].1, vec![strokes[0].clone()]);
+        assert_eq!(stages[1].1[0].points().count(), 3);
+        assert!(stages[3].1[0]
+            .points()
+            .all(|p| (0.0..=1.0).contains(&p.x) && (0.0..=1.0).contains(&p.y)));
+
+        let sample = StrokeSample::new(strokes).unwrap();
+        assert_eq!(stages[6].1, sample.strokes());
+        assert!(StrokeSample::stages(vec![]).is_none());
+    }
+
     #[test]
     fn test_bad_samples() {
         let strokes = Vec::new();
@@ -0,0 +1,267 @@
+//! SVG rendering of strokes, to see what preprocessing does to a drawing
+
+use crate::{Point, Stage, Stroke, StrokeSample};
+use std::fmt::Write;
+
+const PANEL_SIZE: f64 = 240.0;
+const MARGIN: f64 = 24.0;
+const COLORS: [&str; 10] = [
+    "#1f77b4", "#ff7f0e", "#2ca02c", "#d62728", "#9467bd", "#8c564b", "#e377c2", "#7f7f7f",
+    "#bcbd22", "#17becf",
+];
+
+/// Maps points into a panel, keeping their aspect ratio and centering them
+struct Transform {
+    scale: f64,
+    offset: Point,
+}
+
+impl Transform {
+    fn new(min: Point, max: Point, panel: usize) -> Transform {
+        let size = (max.x - min.x).max(max.y - min.y);
+        let scale = if size > 0.0 {
+            (PANEL_SIZE - 2.0 * MARGIN) / size
+        } else {
+            1.0
+        };
+        let center = (min + max) * 0.5;
+        let panel_center = Point {
+            x: (panel as f64 + 0.5) * PANEL_SIZE,
+            y: 0.5 * PANEL_SIZE,
+        };
+
+        Transform {
+            scale,
+            offset: panel_center - center * scale,
+        }
+    }
+
+    /// Fits the bounding box of every point of `strokes`
+    fn fit(strokes: &[Stroke], panel: usize) -> Transform {
+        let mut points = strokes.iter().flat_map(Stroke::points);
+        let first = match points.next() {
+            Some(&point) => point,
+            None => {
+                return Transform::new(Point { x: 0.0, y: 0.0 }, Point { x: 1.0, y: 1.0 }, panel)
+            }
+        };
+
+        let (min, max) = points.fold((first, first), |(min, max), p| {
+            (
+                Point {
+                    x: min.x.min(p.x),
+                    y: min.y.min(p.y),
+                },
+                Point {
+                    x: max.x.max(p.x),
+                    y: max.y.max(p.y),
+                },
+            )
+        });
+
+        Transform::new(min, max, panel)
+    }
+
+    fn apply(&self, point: Point) -> Point {
+        point * self.scale + self.offset
+    }
+}
+
+/// Draws strokes in order of their colors, numbering them at their first point and marking every
+/// point when `dots` is set
+fn panel(
+    svg: &mut String,
+    panel: usize,
+    title: &str,
+    strokes: &[Stroke],
+    transform: &Transform,
+    dots: bool,
+) {
+    let x = panel as f64 * PANEL_SIZE;
+    writeln!(
+        svg,
+        r##"<rect x="{}" y="0" width="{}" height="{}" fill="none" stroke="#ccc"/>"##,
+        x, PANEL_SIZE, PANEL_SIZE
+    )
+    .unwrap();
+    writeln!(
+        svg,
+        r##"<text x="{}" y="16" font-family="sans-serif" font-size="12" fill="#666">{}</text>"##,
+        x + 6.0,
+        title
+    )
+    .unwrap();
+
+    for (i, stroke) in strokes.iter().enumerate() {
+        let color = COLORS[i % COLORS.len()];
+        let points: Vec<Point> = stroke.points().map(|&p| transform.apply(p)).collect();
+        let start = match points.first() {
+            Some(&start) => start,
+            None => continue,
+        };
+
+        let path: Vec<String> = points
+            .iter()
+            .map(|p| format!("{:.2},{:.2}", p.x, p.y))
+            .collect();
+        writeln!(
+            svg,
+            r#"<polyline points="{}" fill="none" stroke="{}" stroke-width="2" stroke-linecap="round" stroke-linejoin="round"/>"#,
+            path.join(" "),
+            color
+        )
+        .unwrap();
+
+        if dots {
+            for p in &points {
+                writeln!(
+                    svg,
+                    r#"<circle cx="{:.2}" cy="{:.2}" r="2.5" fill="{}"/>"#,
+                    p.x, p.y, color
+                )
+                .unwrap();
+            }
+        }
+
+        writeln!(
+            svg,
+            r#"<circle cx="{:.2}" cy="{:.2}" r="5" fill="none" stroke="{}" stroke-width="1.5"/>"#,
+            start.x, start.y, color
+        )
+        .unwrap();
+        writeln!(
+            svg,
+            r#"<text x="{:.2}" y="{:.2}" font-family="sans-serif" font-size="11" fill="{}">{}</text>"#,
+            start.x + 7.0,
+            start.y - 7.0,
+            color,
+            i + 1
+        )
+        .unwrap();
+    }
+}
+
+fn document(panels: usize, content: &str) -> String {
+    format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{0}\" height=\"{1}\" viewBox=\"0 0 {0} {1}\">\n\
+         <rect width=\"100%\" height=\"100%\" fill=\"white\"/>\n{2}</svg>\n",
+        panels as f64 * PANEL_SIZE,
+        PANEL_SIZE,
+        content
+    )
+}
+
+/// Renders strokes as they were drawn, numbered in the order they were drawn
+pub fn render_strokes(strokes: &[Stroke]) -> String {
+    let mut svg = String::new();
+    input_panel(&mut svg, 0, strokes);
+    document(1, &svg)
+}
+
+/// Renders the points of a preprocessed sample, every stroke has been refit into the unit square
+pub fn render_sample(sample: &StrokeSample) -> String {
+    let mut svg = String::new();
+    sample_panel(&mut svg, 0, sample);
+    document(1, &svg)
+}
+
+fn input_panel(svg: &mut String, index: usize, strokes: &[Stroke]) {
+    let transform = Transform::fit(strokes, index);
+    panel(svg, index, "input", strokes, &transform, false);
+}
+
+fn sample_panel(svg: &mut String, index: usize, sample: &StrokeSample) {
+    let transform = Transform::new(Point { x: 0.0, y: 0.0 }, Point { x: 1.0, y: 1.0 }, index);
+    panel(
+        svg,
+        index,
+        "preprocessed",
+        sample.strokes(),
+        &transform,
+        true,
+    );
+}
+
+/// Renders strokes as they were drawn next to the sample they preprocess to, the second panel is
+/// left empty if the strokes have no points
+pub fn render_preprocessing(strokes: &[Stroke]) -> String {
+    let mut svg = String::new();
+    input_panel(&mut svg, 0, strokes);
+    match StrokeSample::new(strokes.to_vec()) {
+        Some(sample) => sample_panel(&mut svg, 1, &sample),
+        None => panel(
+            &mut svg,
+            1,
+            "preprocessed",
+            &[],
+            &Transform::fit(&[], 1),
+            false,
+        ),
+    }
+    document(2, &svg)
+}
+
+/// Renders the strokes after every preprocessing stage side by side, see
+/// [`StrokeSample::stages`]. Stages from [`Stage::AspectRefit`] on are drawn in the unit square.
+pub fn render_stages(strokes: &[Stroke]) -> String {
+    let stages = StrokeSample::stages(strokes.to_vec()).unwrap_or_default();
+
+    let mut svg = String::new();
+    for (index, (stage, strokes)) in stages.iter().enumerate() {
+        let normalized = !matches!(stage, Stage::Input | Stage::Dedup | Stage::Smooth);
+        let transform = if normalized {
+            Transform::new(Point { x: 0.0, y: 0.0 }, Point { x: 1.0, y: 1.0 }, index)
+        } else {
+            Transform::fit(strokes, index)
+        };
+        panel(&mut svg, index, stage.name(), strokes, &transform, true);
+    }
+    document(stages.len().max(1), &svg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strokes() -> Vec<Stroke> {
+        vec![
+            Stroke::new(vec![
+                Point { x: 10.0, y: 10.0 },
+                Point { x: 50.0, y: 12.0 },
+                Point { x: 90.0, y: 10.0 },
+            ]),
+            Stroke::new(vec![Point { x: 50.0, y: 0.0 }, Point { x: 50.0, y: 40.0 }]),
+        ]
+    }
+
+    #[test]
+    fn test_render_strokes() {
+        let svg = render_strokes(&strokes());
+
+        assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"240\""));
+        assert_eq!(svg.matches("<polyline").count(), 2);
+        assert_eq!(svg.matches("r=\"2.5\"").count(), 0);
+        assert!(svg.contains(">1</text>") && svg.contains(">2</text>"));
+
+        // the drawing is centered and fills the panel horizontally
+        assert!(svg.contains("points=\"24.00,"));
+        assert!(svg.contains(" 216.00,"));
+    }
+
+    #[test]
+    fn test_render_preprocessing() {
+        let svg = render_preprocessing(&strokes());
+        assert!(svg.contains("width=\"480\""));
+        assert_eq!(svg.matches("<polyline").count(), 4);
+        assert!(svg.matches("r=\"2.5\"").count() > 0);
+
+        let svg = render_stages(&strokes());
+        assert!(svg.contains("width=\"1680\""));
+        assert_eq!(svg.matches("<polyline").count(), 14);
+        assert!(svg.contains(">aspect refit</text>"));
+
+        let svg = render_preprocessing(&[]);
+        assert_eq!(svg.matches("<polyline").count(), 0);
+        assert!(svg.contains(">preprocessed</text>"));
+    }
+}
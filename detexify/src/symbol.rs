@@ -1,5 +1,11 @@
 extern crate phf;
 
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
 include!(concat!(env!("OUT_DIR"), "/symbol_table.rs"));
 
 #[derive(Debug, Clone, PartialEq)]
@@ -36,11 +42,151 @@ pub fn iter_symbols() -> impl Iterator<Item = Symbol> {
     SYMBOL_TABLE.values().cloned()
 }
 
+/// Whether a symbol is usable in math or text mode
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Mode {
+    Math,
+    Text,
+}
+
+impl FromStr for Mode {
+    type Err = String;
+
+    fn from_str(mode: &str) -> Result<Mode, String> {
+        match mode {
+            "math" => Ok(Mode::Math),
+            "text" => Ok(Mode::Text),
+            _ => Err(format!("unknown mode {}, expected math or text", mode)),
+        }
+    }
+}
+
+/// Filters symbols by package, mode and a case-insensitive search of the command
+#[derive(Debug, Default)]
+pub struct SymbolFilter {
+    pub packages: Option<Vec<String>>,
+    pub mode: Option<Mode>,
+    pub query: Option<String>,
+}
+
+impl SymbolFilter {
+    pub fn matches(&self, symbol: &Symbol) -> bool {
+        let package = match &self.packages {
+            Some(packages) => packages.iter().any(|p| p == symbol.package),
+            None => true,
+        };
+
+        let mode = match self.mode {
+            Some(Mode::Math) => symbol.math_mode,
+            Some(Mode::Text) => symbol.text_mode,
+            None => true,
+        };
+
+        let query = match &self.query {
+            Some(query) => symbol
+                .command
+                .to_lowercase()
+                .contains(&query.to_lowercase()),
+            None => true,
+        };
+
+        package && mode && query
+    }
+}
+
+/// Converts an id in the base64 encoding used by the original detexify snapshots to the base32
+/// encoding of [`Symbol::id`], `None` if `id` isn't valid base64
+pub fn base64_to_base32(id: &str) -> Option<String> {
+    let id = base64::decode(id).ok()?;
+    Some(base32::encode(
+        base32::Alphabet::RFC4648 { padding: false },
+        &id,
+    ))
+}
+
+/// Converts an id in the base32 encoding of [`Symbol::id`] to the base64 encoding used by the
+/// original detexify snapshots, `None` if `id` isn't valid base32
+pub fn base32_to_base64(id: &str) -> Option<String> {
+    let id = base32::decode(base32::Alphabet::RFC4648 { padding: false }, id)?;
+    Some(base64::encode(&id))
+}
+
+/// Encoding of the symbol ids of a snapshot
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IdEncoding {
+    /// The encoding of [`Symbol::id`]
+    Base32,
+    /// The encoding of the original detexify snapshots
+    Base64,
+}
+
+impl IdEncoding {
+    /// Base64 if the ids naming a symbol only do once converted from base64, base32 if they do
+    /// as they are or no id names a symbol. Errors if ids name symbols in both encodings.
+    pub fn detect<'a, I: IntoIterator<Item = &'a String>>(
+        ids: I,
+    ) -> Result<IdEncoding, MixedIdEncodings> {
+        let (mut base32, mut base64) = (false, false);
+        for id in ids {
+            if Symbol::from_id(id).is_some() {
+                base32 = true;
+            } else if base64_to_base32(id)
+                .and_then(|id| Symbol::from_id(&id))
+                .is_some()
+            {
+                base64 = true;
+            }
+        }
+
+        match (base32, base64) {
+            (true, true) => Err(MixedIdEncodings),
+            (false, true) => Ok(IdEncoding::Base64),
+            _ => Ok(IdEncoding::Base32),
+        }
+    }
+}
+
+impl fmt::Display for IdEncoding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            IdEncoding::Base32 => write!(f, "base32"),
+            IdEncoding::Base64 => write!(f, "base64"),
+        }
+    }
+}
+
+/// Returned by [`IdEncoding::detect`] for snapshots with both base32 and base64 ids
+#[derive(Debug, PartialEq)]
+pub struct MixedIdEncodings;
+
+impl fmt::Display for MixedIdEncodings {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "the snapshot mixes base32 and base64 symbol ids")
+    }
+}
+
+impl Error for MixedIdEncodings {}
+
+/// Converts the ids of snapshot samples to base32 if they are base64, see [`IdEncoding::detect`]
+pub fn to_base32_ids<T>(
+    samples: HashMap<String, T>,
+) -> Result<HashMap<String, T>, MixedIdEncodings> {
+    if IdEncoding::detect(samples.keys())? == IdEncoding::Base32 {
+        return Ok(samples);
+    }
+
+    Ok(samples
+        .into_iter()
+        .map(|(id, value)| (base64_to_base32(&id).unwrap_or(id), value))
+        .collect())
+}
+
 #[cfg(test)]
 mod tests {
 
-    use super::Symbol;
-    use crate::iter_symbols;
+    use super::*;
 
     #[test]
     fn test_from_id() {
@@ -63,10 +209,83 @@ mod tests {
         assert_eq!(iter_symbols().count(), 1098);
     }
 
+    #[test]
+    fn test_id_encodings() {
+        let id = "NRQXIZLYGJSS2T2UGEWV65DFPB2GC43DNFUWG2LSMN2W2";
+        let base64 = base32_to_base64(id).unwrap();
+
+        assert_eq!(base64, "bGF0ZXgyZS1PVDEtX3RleHRhc2NpaWNpcmN1bQ==");
+        assert_eq!(base64_to_base32(&base64).unwrap(), id);
+        assert_eq!(base64_to_base32("not base64!"), None);
+        assert_eq!(base32_to_base64("not base32!"), None);
+    }
+
+    #[test]
+    fn test_to_base32_ids() {
+        let id = "NRQXIZLYGJSS2T2UGEWV65DFPB2GC43DNFUWG2LSMN2W2";
+        let samples = |id: &str| {
+            let mut samples = HashMap::new();
+            samples.insert(id.to_string(), 1);
+            samples.insert("custom".to_string(), 2);
+            samples
+        };
+
+        let base64 = samples(&base32_to_base64(id).unwrap());
+        assert_eq!(IdEncoding::detect(base64.keys()), Ok(IdEncoding::Base64));
+        assert_eq!(to_base32_ids(base64), Ok(samples(id)));
+
+        assert_eq!(
+            IdEncoding::detect(samples(id).keys()),
+            Ok(IdEncoding::Base32)
+        );
+        assert_eq!(to_base32_ids(samples(id)), Ok(samples(id)));
+
+        // whichever id comes first
+        let other = "NRQXIZLYGJSS2T2UGEWV65DFPB2GYZLTOM";
+        let mixed = [id.to_string(), base32_to_base64(other).unwrap()];
+        assert_eq!(IdEncoding::detect(&mixed), Err(MixedIdEncodings));
+        assert_eq!(
+            IdEncoding::detect(mixed.iter().rev()),
+            Err(MixedIdEncodings)
+        );
+    }
+
     #[test]
     fn test_id_get_id() {
         for symbol in iter_symbols() {
             assert_eq!(Symbol::from_id(symbol.id()).unwrap(), symbol);
         }
     }
+
+    #[test]
+    fn test_filter() {
+        let symbol = Symbol {
+            command: "\\textless",
+            package: "latex2e",
+            font_encoding: "OT1",
+            text_mode: true,
+            math_mode: false,
+        };
+
+        assert!(SymbolFilter::default().matches(&symbol));
+        assert!(SymbolFilter {
+            packages: Some(vec!["amssymb".to_string(), "latex2e".to_string()]),
+            mode: Some(Mode::Text),
+            query: Some("LESS".to_string()),
+        }
+        .matches(&symbol));
+        assert!(!SymbolFilter {
+            mode: Some(Mode::Math),
+            ..SymbolFilter::default()
+        }
+        .matches(&symbol));
+        assert!(!SymbolFilter {
+            query: Some("greater".to_string()),
+            ..SymbolFilter::default()
+        }
+        .matches(&symbol));
+
+        assert_eq!("text".parse(), Ok(Mode::Text));
+        assert!("both".parse::<Mode>().is_err());
+    }
 }
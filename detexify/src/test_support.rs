@@ -0,0 +1,6 @@
+use crate::{point::Point, stroke::Stroke, stroke_sample::StrokeSample};
+
+/// A single straight stroke from `from` to `to`
+pub fn line(from: Point, to: Point) -> StrokeSample {
+    StrokeSample::new(vec![Stroke::new(vec![from, (from + to) * 0.5, to])]).unwrap()
+}